@@ -0,0 +1,31 @@
+//! Times instruction decoding.
+//!
+//! Run with `cargo bench --bench decode`. Decodes every valid instruction
+//! word below 100000, over and over, a million words per round.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use adventofcode_2019::intcode::Instruction;
+
+const ROUNDS: usize = 5;
+
+fn main() {
+    let words: Vec<i64> = (0..100_000)
+        .filter(|n| Instruction::try_from(*n).is_ok())
+        .cycle()
+        .take(1_000_000)
+        .collect();
+
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        for n in &words {
+            black_box(Instruction::try_from(black_box(*n))).ok();
+        }
+        best = best.min(start.elapsed());
+    }
+
+    let per = best.as_nanos() as f64 / words.len() as f64;
+    println!("decode {per:>8.2} ns/instruction");
+}
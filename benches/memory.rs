@@ -0,0 +1,202 @@
+//! Compares the Intcode memory backends on the puzzle programs.
+//!
+//! Run with `cargo bench --bench memory`. Puzzle inputs are read from
+//! `input/N` or `$AOC_INPUT_DIR/N`; days without an input file are
+//! skipped, and a synthetic counting loop always runs.
+
+use std::time::{Duration, Instant};
+
+use adventofcode_2019::input;
+use adventofcode_2019::intcode::{
+    asm, DenseMemory, Intcode, IntcodeError, Memory, RunResult, SparseMemory,
+};
+use itertools::Itertools;
+
+type Workload = fn(&[i64]) -> Result<i64, IntcodeError>;
+
+const ROUNDS: usize = 5;
+
+fn machine<M>(program: &[i64]) -> Intcode<M>
+where
+    M: Memory + for<'a> From<&'a [i64]>,
+{
+    Intcode::with_memory(M::from(program))
+}
+
+fn day2<M>(program: &[i64]) -> Result<i64, IntcodeError>
+where
+    M: Memory + for<'a> From<&'a [i64]>,
+{
+    let mut program = program.to_vec();
+    let mut found = 0;
+
+    for noun in 0..=99 {
+        for verb in 0..=99 {
+            program[1] = noun;
+            program[2] = verb;
+
+            let mut intcode = machine::<M>(&program);
+            intcode.try_run()?;
+            if intcode.get_mem_range(0, 1)[0] == 19690720 {
+                found = 100 * noun + verb;
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+fn day5<M>(program: &[i64]) -> Result<i64, IntcodeError>
+where
+    M: Memory + for<'a> From<&'a [i64]>,
+{
+    let mut intcode = machine::<M>(program);
+    intcode.input(5);
+    intcode.try_run()?;
+    Ok(intcode.output().unwrap_or_default())
+}
+
+fn day7<M>(program: &[i64]) -> Result<i64, IntcodeError>
+where
+    M: Memory + for<'a> From<&'a [i64]>,
+{
+    let mut max = i64::MIN;
+
+    for perm in (5..=9).permutations(5) {
+        let mut amps: Vec<Intcode<M>> = perm
+            .iter()
+            .map(|p| {
+                let mut intcode = machine::<M>(program);
+                intcode.input(*p);
+                intcode
+            })
+            .collect();
+
+        let mut signal = 0;
+        'feedback: loop {
+            for (i, amp) in amps.iter_mut().enumerate() {
+                amp.input(signal);
+                let result = amp.try_run()?;
+                signal = amp.output().unwrap_or_default();
+                if result == RunResult::Halted && i == 4 {
+                    break 'feedback;
+                }
+            }
+        }
+
+        max = max.max(signal);
+    }
+
+    Ok(max)
+}
+
+fn day9<M>(program: &[i64]) -> Result<i64, IntcodeError>
+where
+    M: Memory + for<'a> From<&'a [i64]>,
+{
+    let mut intcode = machine::<M>(program);
+    intcode.input(2);
+    intcode.try_run()?;
+    Ok(intcode.output().unwrap_or_default())
+}
+
+fn countdown<M>(program: &[i64]) -> Result<i64, IntcodeError>
+where
+    M: Memory + for<'a> From<&'a [i64]>,
+{
+    let mut intcode = machine::<M>(program);
+    intcode.input(1_000_000);
+    intcode.try_run()?;
+    Ok(intcode.output().unwrap_or_default())
+}
+
+fn time(workload: Workload, program: &[i64]) -> Result<(Duration, i64), IntcodeError> {
+    let mut best = Duration::MAX;
+    let mut answer = 0;
+
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        answer = workload(program)?;
+        best = best.min(start.elapsed());
+    }
+
+    Ok((best, answer))
+}
+
+fn compare(name: &str, program: &[i64], sparse: Workload, dense: Workload) {
+    match (time(sparse, program), time(dense, program)) {
+        (Ok((sparse, a)), Ok((dense, b))) => {
+            assert_eq!(a, b, "{name}: backends disagree");
+            println!(
+                "{name:<10} {:>12.3?} {:>12.3?} {:>8.2}x",
+                sparse,
+                dense,
+                sparse.as_secs_f64() / dense.as_secs_f64()
+            );
+        }
+        (Err(e), _) | (_, Err(e)) => println!("{name:<10} failed: {e}"),
+    }
+}
+
+fn load(day: u32) -> Option<Vec<i64>> {
+    let path = input::path(day);
+    let s = match input::load(day) {
+        Ok(Some(s)) => s,
+        Ok(None) => {
+            println!("day{day:<7} skipped: {} not found", path.display());
+            return None;
+        }
+        Err(e) => {
+            println!("day{day:<7} skipped: {}: {e}", path.display());
+            return None;
+        }
+    };
+
+    match s.parse::<Intcode>() {
+        Ok(intcode) => {
+            let end = intcode.snapshot().memory().end();
+            Some(intcode.get_mem_range(0, end))
+        }
+        Err(e) => {
+            println!("day{day:<7} skipped: {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn main() {
+    println!(
+        "{:<10} {:>12} {:>12} {:>9}",
+        "workload", "sparse", "dense", "speedup"
+    );
+
+    let counter = asm::assemble(
+        "       in [n]
+         loop:  add [n], #-1, [n]
+                add [rb+100], #1, [rb+100]
+                jnz [n], #loop
+                out [rb+100]
+                hlt
+         n:     .data 0",
+    )
+    .unwrap();
+    compare(
+        "countdown",
+        &counter,
+        countdown::<SparseMemory>,
+        countdown::<DenseMemory>,
+    );
+
+    let days: [(u32, Workload, Workload); 4] = [
+        (2, day2::<SparseMemory>, day2::<DenseMemory>),
+        (5, day5::<SparseMemory>, day5::<DenseMemory>),
+        (7, day7::<SparseMemory>, day7::<DenseMemory>),
+        (9, day9::<SparseMemory>, day9::<DenseMemory>),
+    ];
+
+    for (day, sparse, dense) in days {
+        if let Some(program) = load(day) {
+            compare(&format!("day{day}"), &program, sparse, dense);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Known answers, for checking solvers against many people's inputs.
+//!
+//! Everyone gets a different puzzle input, so answers are recorded against
+//! a fingerprint of the input they belong to: the 64-bit FNV-1a hash of
+//! the input without trailing whitespace. [`FILE`] holds one answer per
+//! line, as `DAY PART FINGERPRINT ANSWER` with the fingerprint in hex.
+//! Blank lines and lines starting with `#` are ignored.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::parse;
+use crate::solution::{Day, Part};
+use crate::ParseError;
+
+pub const FILE: &str = "answers.txt";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Known {
+    answers: BTreeMap<(u32, Part, u64), String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Fail { expected: String },
+    Unknown,
+}
+
+/// The answer a solver gave for one part of an input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub day: u32,
+    pub part: Part,
+    pub fingerprint: u64,
+    pub answer: String,
+    pub verdict: Verdict,
+}
+
+pub fn fingerprint(input: &str) -> u64 {
+    input
+        .trim_end()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+impl Known {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut known = Known::default();
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let mut field = |expected| {
+                fields
+                    .next()
+                    .ok_or_else(|| ParseError::at(s, &line[line.len()..], expected))
+            };
+
+            let day = parse::number(s, field("a day")?)?;
+            let part = field("a part")?;
+            let part = parse::number(s, part)
+                .ok()
+                .and_then(|n: u32| Part::try_from(n).ok())
+                .ok_or_else(|| ParseError::at(s, part, "part 1 or 2"))?;
+            let fingerprint = field("a fingerprint")?;
+            let fingerprint = u64::from_str_radix(fingerprint, 16)
+                .map_err(|_| ParseError::at(s, fingerprint, "a hex fingerprint"))?;
+            let answer = field("an answer")?;
+            if let Some(extra) = fields.next() {
+                return Err(ParseError::at(s, extra, "the end of the line"));
+            }
+
+            known.insert(day, part, fingerprint, answer);
+        }
+
+        Ok(known)
+    }
+
+    pub fn get(&self, day: u32, part: Part, fingerprint: u64) -> Option<&str> {
+        self.answers
+            .get(&(day, part, fingerprint))
+            .map(String::as_str)
+    }
+
+    pub fn insert(&mut self, day: u32, part: Part, fingerprint: u64, answer: &str) {
+        self.answers
+            .insert((day, part, fingerprint), answer.to_string());
+    }
+
+    /// Solves both parts of `input` and compares the answers with the
+    /// known ones.
+    pub fn check(&self, day: &Day, input: &str) -> Result<Vec<Outcome>, ParseError> {
+        let puzzle = day.parse(input)?;
+        let fingerprint = fingerprint(input);
+
+        Ok(Part::ALL
+            .into_iter()
+            .map(|part| {
+                let answer = puzzle.answer(part);
+                let verdict = match self.get(day.day, part, fingerprint) {
+                    Some(expected) if expected == answer => Verdict::Pass,
+                    Some(expected) => Verdict::Fail {
+                        expected: expected.to_string(),
+                    },
+                    None => Verdict::Unknown,
+                };
+
+                Outcome {
+                    day: day.day,
+                    part,
+                    fingerprint,
+                    answer,
+                    verdict,
+                }
+            })
+            .collect())
+    }
+}
+
+/// The known answer to `part` of `input` in a day's tests, or says the
+/// test is skipped if there is none.
+///
+/// The message goes straight to stderr so the test harness does not
+/// capture it.
+#[cfg(test)]
+pub(crate) fn for_test(day: u32, part: Part, input: &str) -> Option<String> {
+    use std::io::{self, Write};
+
+    let known = Known::parse(&std::fs::read_to_string(FILE).unwrap()).unwrap();
+    let fingerprint = fingerprint(input);
+    let answer = known.get(day, part, fingerprint).map(str::to_string);
+    if answer.is_none() {
+        let _ = writeln!(
+            io::stderr(),
+            "skipping day {day} part {part} input test: no answer for input {fingerprint:016x} in {FILE} (see aoc-check --record)"
+        );
+    }
+    answer
+}
+
+/// Writes the outcome as a line of [`FILE`].
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:016x} {}",
+            self.day, self.part, self.fingerprint, self.answer
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input, solution};
+    use std::fs;
+
+    fn known() -> Known {
+        Known::parse(&fs::read_to_string(FILE).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn fingerprints() {
+        assert_eq!(fingerprint(""), 0xcbf29ce484222325);
+        assert_eq!(fingerprint("a\n"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |s| Known::parse(s).unwrap_err().to_string();
+        assert_eq!(
+            err("# day 1\n1 3 ff 12"),
+            "line 2, column 3: expected part 1 or 2, found '3'"
+        );
+        assert_eq!(
+            err("1 1 xyz 12"),
+            "line 1, column 5: expected a hex fingerprint, found 'xyz'"
+        );
+        assert_eq!(
+            err("1 1 ff"),
+            "line 1, column 7: expected an answer, found nothing"
+        );
+        assert_eq!(
+            err("1 1 ff 12 13"),
+            "line 1, column 11: expected the end of the line, found '13'"
+        );
+    }
+
+    #[test]
+    fn examples() {
+        let known = Known::parse("1 1 591ea9a19d2a7583 34241\n1 2 591ea9a19d2a7583 51316").unwrap();
+        let day = solution::day(1).unwrap();
+
+        let outcomes = known.check(day, "12\n14\n1969\n100756\n").unwrap();
+        assert!(outcomes.iter().all(|o| o.verdict == Verdict::Pass));
+        assert_eq!(outcomes[1].to_string(), "1 2 591ea9a19d2a7583 51316");
+
+        let mut wrong = known.clone();
+        wrong.insert(1, Part::One, outcomes[0].fingerprint, "42");
+        assert_eq!(
+            wrong.check(day, "12\n14\n1969\n100756").unwrap()[0].verdict,
+            Verdict::Fail {
+                expected: "42".to_string()
+            }
+        );
+
+        let outcomes = known.check(day, "12").unwrap();
+        assert!(outcomes.iter().all(|o| o.verdict == Verdict::Unknown));
+    }
+
+    /// Checks every input present against the known answers.
+    #[test]
+    fn inputs() {
+        let known = known();
+
+        for day in solution::days() {
+            let Some(input) = input::load(day.day).unwrap() else {
+                continue;
+            };
+            for outcome in known.check(day, &input).unwrap() {
+                if let Verdict::Fail { expected } = &outcome.verdict {
+                    panic!(
+                        "day {} part {}: got {}, expected {expected}",
+                        outcome.day, outcome.part, outcome.answer
+                    );
+                }
+            }
+        }
+    }
+}
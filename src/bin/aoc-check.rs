@@ -0,0 +1,151 @@
+//! Check solvers against known answers.
+//!
+//! ```text
+//! aoc-check [--answers FILE] [--record] [DIR...]
+//! ```
+//!
+//! Solves every input found in each DIR, which defaults to `input` or
+//! `$AOC_INPUT_DIR`, and compares the answers with those in `answers.txt`.
+//! Each answer is reported as passing, failing or unknown, along with the
+//! input's fingerprint. `--record` appends the unknown answers to the
+//! answers file. The exit status is 1 if any answer failed.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use adventofcode_2019::answers::{self, Known, Verdict};
+use adventofcode_2019::{input, solution};
+
+const USAGE: &str = "\
+usage: aoc-check [--answers FILE] [--record] [DIR...]
+
+  -f, --answers FILE   read known answers from FILE instead of answers.txt
+  -r, --record         add unknown answers to the answers file";
+
+struct Options {
+    answers: PathBuf,
+    record: bool,
+    dirs: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("aoc-check: {e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(options) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("aoc-check: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns `None` if help was asked for.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        answers: PathBuf::from(answers::FILE),
+        record: false,
+        dirs: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--answers" => {
+                options.answers = args.next().ok_or("--answers needs a value")?.into()
+            }
+            "-r" | "--record" => options.record = true,
+            "-h" | "--help" => return Ok(None),
+            other if other.starts_with('-') => return Err(format!("unknown option '{other}'")),
+            _ => options.dirs.push(arg.into()),
+        }
+    }
+
+    if options.dirs.is_empty() {
+        options.dirs.push(input::dir());
+    }
+    Ok(Some(options))
+}
+
+/// Returns whether every known answer matched.
+fn run(options: Options) -> Result<bool, String> {
+    let path = options.answers.display();
+    let text = fs::read_to_string(&options.answers).map_err(|e| format!("{path}: {e}"))?;
+    let known = Known::parse(&text).map_err(|e| format!("{path}: {e}"))?;
+
+    let (mut passed, mut failed, mut unknown) = (0, 0, Vec::new());
+
+    for dir in &options.dirs {
+        for day in solution::days() {
+            let file = dir.join(day.day.to_string());
+            let error = |e: &dyn std::fmt::Display| format!("{}: {e}", file.display());
+
+            let Some(input) = input::load_from(dir, day.day).map_err(|e| error(&e))? else {
+                continue;
+            };
+            let outcomes = match known.check(day, &input) {
+                Ok(outcomes) => outcomes,
+                Err(e) => {
+                    println!("{}: invalid input: {e}", file.display());
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            for outcome in outcomes {
+                let verdict = match &outcome.verdict {
+                    Verdict::Pass => "ok".to_string(),
+                    Verdict::Fail { expected } => format!("FAIL, expected {expected}"),
+                    Verdict::Unknown => "unknown".to_string(),
+                };
+                println!(
+                    "{} [{:016x}] part {}: {} {verdict}",
+                    file.display(),
+                    outcome.fingerprint,
+                    outcome.part,
+                    outcome.answer,
+                );
+
+                match outcome.verdict {
+                    Verdict::Pass => passed += 1,
+                    Verdict::Fail { .. } => failed += 1,
+                    Verdict::Unknown => unknown.push(outcome),
+                }
+            }
+        }
+    }
+
+    println!(
+        "{passed} passed, {failed} failed, {} unknown",
+        unknown.len()
+    );
+
+    if options.record && !unknown.is_empty() {
+        let record = || -> io::Result<()> {
+            let mut file = OpenOptions::new().append(true).open(&options.answers)?;
+            if !text.is_empty() && !text.ends_with('\n') {
+                writeln!(file)?;
+            }
+            for outcome in &unknown {
+                writeln!(file, "{outcome}")?;
+            }
+            Ok(())
+        };
+        record().map_err(|e| format!("{path}: {e}"))?;
+        println!("recorded {} answers in {path}", unknown.len());
+    }
+
+    Ok(failed == 0)
+}
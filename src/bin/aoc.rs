@@ -0,0 +1,196 @@
+//! Solve puzzles.
+//!
+//! ```text
+//! aoc --day N [--part 1|2] [--input PATH]
+//! aoc --all [--part 1|2]
+//! ```
+//!
+//! Input is read from `input/N`, or `$AOC_INPUT_DIR/N` if that is set,
+//! unless `--input` says otherwise. Both parts are solved unless `--part`
+//! picks one. Answers are printed along with how long parsing and solving
+//! took; `--all` prints a table of every day instead, skipping days
+//! without an input file or whose input does not parse.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use adventofcode_2019::input;
+use adventofcode_2019::solution::{self, Day, Part};
+use adventofcode_2019::ParseError;
+
+const USAGE: &str = "\
+usage: aoc --day N [--part 1|2] [--input PATH]
+       aoc --all [--part 1|2]
+
+  -d, --day N        solve day N
+  -p, --part 1|2     solve only this part
+  -i, --input PATH   read input from PATH instead of input/N
+                     or $AOC_INPUT_DIR/N
+  -a, --all          solve every day";
+
+#[derive(Default)]
+struct Options {
+    day: Option<u32>,
+    part: Option<Part>,
+    input: Option<String>,
+    all: bool,
+}
+
+struct Timed {
+    part: Part,
+    answer: String,
+    solve: Duration,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("aoc: {e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = if options.all {
+        all(options.part)
+    } else {
+        one(options)
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("aoc: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns `None` if help was asked for.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+
+        match arg.as_str() {
+            "-d" | "--day" => options.day = Some(number(&value(&arg)?)?),
+            "-p" | "--part" => {
+                let part: u32 = number(&value(&arg)?)?;
+                let part = Part::try_from(part).map_err(|n| format!("there is no part {n}"))?;
+                options.part = Some(part);
+            }
+            "-i" | "--input" => options.input = Some(value(&arg)?),
+            "-a" | "--all" => options.all = true,
+            "-h" | "--help" => return Ok(None),
+            other => return Err(format!("unexpected argument '{other}'")),
+        }
+    }
+
+    match (options.all, options.day, &options.input) {
+        (true, Some(_), _) | (true, _, Some(_)) => {
+            Err("--all cannot be combined with --day or --input".to_string())
+        }
+        (false, None, _) => Err("no day given".to_string()),
+        _ => Ok(Some(options)),
+    }
+}
+
+fn one(options: Options) -> Result<(), String> {
+    let n = options.day.unwrap();
+    let day = solution::day(n).ok_or(format!("day {n} is not solved yet"))?;
+    let path = options.input.map_or(input::path(n), PathBuf::from);
+    let input = input::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    let (parse, parts) =
+        solve(day, &input, options.part).map_err(|e| format!("{}: {e}", path.display()))?;
+    println!("day {n}: parsed in {parse:.1?}");
+    for t in parts {
+        println!(
+            "day {n} part {}: {}    (solved in {:.1?})",
+            t.part, t.answer, t.solve
+        );
+    }
+    Ok(())
+}
+
+fn all(part: Option<Part>) -> Result<(), String> {
+    println!(
+        "{:>3}  {:>4}  {:<20} {:>10} {:>10}",
+        "day", "part", "answer", "parse", "solve"
+    );
+
+    let mut total = Duration::ZERO;
+    for day in solution::days() {
+        let input = match input::load(day.day) {
+            Ok(Some(input)) => input,
+            Ok(None) => {
+                println!("{:>3}  {:>4}  (no input)", day.day, "-");
+                continue;
+            }
+            Err(e) => return Err(format!("{}: {e}", input::path(day.day).display())),
+        };
+
+        let (parse, parts) = match solve(day, &input, part) {
+            Ok(solved) => solved,
+            Err(e) => {
+                println!("{:>3}  {:>4}  (invalid input: {e})", day.day, "-");
+                continue;
+            }
+        };
+        total += parse;
+        for (i, t) in parts.iter().enumerate() {
+            let parse = if i == 0 {
+                format!("{parse:.1?}")
+            } else {
+                String::new()
+            };
+            println!(
+                "{:>3}  {:>4}  {:<20} {:>10} {:>10}",
+                day.day,
+                t.part,
+                t.answer,
+                parse,
+                format!("{:.1?}", t.solve)
+            );
+            total += t.solve;
+        }
+    }
+
+    println!("{:<42} {:>10}", "total", format!("{total:.1?}"));
+    Ok(())
+}
+
+/// Parses `input` and solves the chosen parts, timing each step.
+fn solve(day: &Day, input: &str, part: Option<Part>) -> Result<(Duration, Vec<Timed>), ParseError> {
+    let start = Instant::now();
+    let puzzle = day.parse(input)?;
+    let parse = start.elapsed();
+
+    let parts = Part::ALL
+        .into_iter()
+        .filter(|p| part.is_none_or(|part| part == *p))
+        .map(|part| {
+            let start = Instant::now();
+            let answer = puzzle.answer(part);
+            Timed {
+                part,
+                answer,
+                solve: start.elapsed(),
+            }
+        })
+        .collect();
+
+    Ok((parse, parts))
+}
+
+fn number<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("invalid number '{s}'"))
+}
@@ -0,0 +1,77 @@
+//! Play an ASCII Intcode program from the terminal.
+//!
+//! ```text
+//! intcode-play PROGRAM [--log TRANSCRIPT]
+//! intcode-play PROGRAM --replay TRANSCRIPT
+//! ```
+//!
+//! See `adventofcode_2019::intcode::play` for the commands and the
+//! transcript format. Replaying exits with status 1 if the program's
+//! output no longer matches the transcript.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::process::ExitCode;
+
+use adventofcode_2019::intcode::play::{self, Replay, Session};
+use adventofcode_2019::intcode::Intcode;
+
+const USAGE: &str = "usage: intcode-play PROGRAM [--log TRANSCRIPT | --replay TRANSCRIPT]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let (program, option) = match &args[..] {
+        [program] => (program, None),
+        [program, flag, path] if flag == "--log" || flag == "--replay" => {
+            (program, Some((flag.as_str(), path)))
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(program, option) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("intcode-play: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(program: &str, option: Option<(&str, &String)>) -> io::Result<ExitCode> {
+    let vm: Intcode = fs::read_to_string(program)?
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{program}: {e}")))?;
+
+    if let Some(("--replay", path)) = option {
+        let result = play::replay(vm, BufReader::new(File::open(path)?))?;
+        return Ok(match result {
+            Replay::Passed { commands } => {
+                println!("ok: {commands} commands replayed");
+                ExitCode::SUCCESS
+            }
+            Replay::Failed {
+                command,
+                expected,
+                actual,
+            } => {
+                println!("mismatch before command {}", command + 1);
+                println!("expected:");
+                expected.iter().for_each(|line| println!("| {line}"));
+                println!("actual:");
+                actual.iter().for_each(|line| println!("| {line}"));
+                ExitCode::FAILURE
+            }
+        });
+    }
+
+    let mut session = Session::new(vm);
+    if let Some((_, path)) = option {
+        session.log_to(BufWriter::new(File::create(path)?));
+    }
+    session.play(io::stdin().lock(), io::stdout().lock())?;
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,236 @@
+//! Run an Intcode program.
+//!
+//! ```text
+//! intcode [OPTIONS] PROGRAM
+//!
+//!   -i, --input VALUES     queue input values, separated by commas or spaces
+//!   -l, --line TEXT        queue a line of ASCII input
+//!   -s, --stdin            queue everything on stdin: numbers, or text
+//!                          with --ascii
+//!   -a, --ascii            print output as text; values outside the ASCII
+//!                          range are printed as numbers on their own line
+//!   -p, --patch ADDR=VAL   set memory before running, e.g. -p 1=12 -p 2=2
+//!   -m, --mem ADDR         print the value at ADDR after running
+//!   -n, --max-steps N      give up after executing N instructions
+//!   -g, --cfg              print the control flow graph in Graphviz DOT
+//!                          format instead of running
+//!   -r, --rust             print the program transpiled to a Rust module
+//!                          instead of running
+//! ```
+//!
+//! A PROGRAM of `-` is read from stdin. Input is queued in the order the
+//! options are given, with stdin last.
+//!
+//! The exit status says how the program stopped: 0 if it halted, 2 if it
+//! needed more input, 3 if it ran out of steps, and 1 on any error.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use adventofcode_2019::intcode::{Intcode, RunResult};
+
+const USAGE: &str = "\
+usage: intcode [OPTIONS] PROGRAM
+
+  -i, --input VALUES     queue input values, separated by commas or spaces
+  -l, --line TEXT        queue a line of ASCII input
+  -s, --stdin            queue everything on stdin: numbers, or text with --ascii
+  -a, --ascii            print output as text
+  -p, --patch ADDR=VAL   set memory before running
+  -m, --mem ADDR         print the value at ADDR after running
+  -n, --max-steps N      give up after executing N instructions
+  -g, --cfg              print the control flow graph instead of running
+  -r, --rust             print the program as Rust instead of running";
+
+enum Input {
+    Values(Vec<i64>),
+    Line(String),
+}
+
+#[derive(Default)]
+struct Options {
+    program: Option<String>,
+    input: Vec<Input>,
+    stdin: bool,
+    ascii: bool,
+    patches: Vec<(usize, i64)>,
+    dumps: Vec<usize>,
+    max_steps: Option<u64>,
+    cfg: bool,
+    rust: bool,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("intcode: {e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(options) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("intcode: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns `None` if help was asked for.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+
+        match arg.as_str() {
+            "-i" | "--input" => options.input.push(Input::Values(numbers(&value(&arg)?)?)),
+            "-l" | "--line" => options.input.push(Input::Line(value(&arg)?)),
+            "-s" | "--stdin" => options.stdin = true,
+            "-a" | "--ascii" => options.ascii = true,
+            "-p" | "--patch" => {
+                let patch = value(&arg)?;
+                let (addr, val) = patch
+                    .split_once('=')
+                    .ok_or(format!("invalid patch '{patch}', expected ADDR=VAL"))?;
+                options.patches.push((number(addr)?, number(val)?));
+            }
+            "-m" | "--mem" => options.dumps.push(number(&value(&arg)?)?),
+            "-n" | "--max-steps" => options.max_steps = Some(number(&value(&arg)?)?),
+            "-g" | "--cfg" => options.cfg = true,
+            "-r" | "--rust" => options.rust = true,
+            "-h" | "--help" => return Ok(None),
+            other if other.starts_with('-') && other != "-" => {
+                return Err(format!("unknown option '{other}'"))
+            }
+            _ if options.program.is_some() => return Err(format!("unexpected argument '{arg}'")),
+            _ => options.program = Some(arg),
+        }
+    }
+
+    match options.program.as_deref() {
+        None => Err("no program given".to_string()),
+        Some("-") if options.stdin => Err("stdin cannot hold both program and input".to_string()),
+        _ => Ok(Some(options)),
+    }
+}
+
+fn run(options: Options) -> io::Result<ExitCode> {
+    let program = options.program.unwrap();
+    let source = match program.as_str() {
+        "-" => io::read_to_string(io::stdin())?,
+        path => fs::read_to_string(path)?,
+    };
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let mut vm: Intcode = source
+        .parse()
+        .map_err(|e| invalid(format!("{program}: {e}")))?;
+
+    for (addr, val) in options.patches {
+        vm.set_mem(addr, val);
+    }
+
+    if options.cfg {
+        print!("{}", vm.cfg().dot());
+        return Ok(ExitCode::SUCCESS);
+    }
+    if options.rust {
+        print!("{}", vm.transpile());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for input in options.input {
+        match input {
+            Input::Values(values) => values.into_iter().for_each(|v| vm.input(v)),
+            Input::Line(line) => vm.input_line(&line),
+        }
+    }
+
+    if options.stdin {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        if options.ascii {
+            text.chars().for_each(|c| vm.input(c as i64));
+        } else {
+            numbers(&text)
+                .map_err(|e| invalid(format!("stdin: {e}")))?
+                .into_iter()
+                .for_each(|v| vm.input(v));
+        }
+    }
+
+    let result = match options.max_steps {
+        Some(max) => vm.try_run_for(max),
+        None => vm.try_run(),
+    };
+
+    let mut out = io::stdout().lock();
+    let mut at_line_start = true;
+    while let Some(value) = vm.output() {
+        match u8::try_from(value)
+            .ok()
+            .filter(|c| options.ascii && c.is_ascii())
+        {
+            Some(c) => {
+                out.write_all(&[c])?;
+                at_line_start = c == b'\n';
+            }
+            None => {
+                if !at_line_start {
+                    writeln!(out)?;
+                }
+                writeln!(out, "{value}")?;
+                at_line_start = true;
+            }
+        }
+    }
+    if !at_line_start {
+        writeln!(out)?;
+    }
+    for addr in options.dumps {
+        writeln!(out, "[{addr}] = {}", vm.get_mem(addr))?;
+    }
+    out.flush()?;
+
+    Ok(match result {
+        Ok(RunResult::Halted) => ExitCode::SUCCESS,
+        Ok(RunResult::BlockedOnInput) => {
+            eprintln!("intcode: blocked on input at ip {}", vm.ip());
+            ExitCode::from(2)
+        }
+        Ok(RunResult::BudgetExhausted) => {
+            eprintln!(
+                "intcode: stopped after {} steps at ip {}",
+                vm.cycles(),
+                vm.ip()
+            );
+            ExitCode::from(3)
+        }
+        Ok(RunResult::Ok) => unreachable!("running stops only when the machine does"),
+        Err(e) => {
+            eprintln!("intcode: {e}");
+            ExitCode::FAILURE
+        }
+    })
+}
+
+fn numbers(s: &str) -> Result<Vec<i64>, String> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .map(number)
+        .collect()
+}
+
+fn number<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("invalid number '{s}'"))
+}
@@ -1,5 +1,8 @@
-pub struct Puzzle<'a> {
-    s: &'a str,
+use crate::parse;
+use crate::{ParseError, Solution};
+
+pub struct Puzzle {
+    masses: Vec<i32>,
 }
 
 fn required_fuel(mass: i32) -> i32 {
@@ -16,29 +19,47 @@ fn required_fuel2(mass: i32) -> i32 {
     required + required_fuel2(required)
 }
 
-impl<'a> Puzzle<'a> {
-    pub fn new(s: &'a str) -> Self {
-        Puzzle { s }
-    }
+impl Solution<'_> for Puzzle {
+    type Answer1 = i32;
+    type Answer2 = i32;
 
-    pub fn solve(&self) -> i32 {
-        self.s
+    fn new(s: &str) -> Result<Self, ParseError> {
+        let masses = s
             .lines()
-            .map(|l| required_fuel(l.parse::<i32>().unwrap()))
-            .sum()
+            .map(|l| parse::number(s, l.trim()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Puzzle { masses })
     }
 
-    pub fn solve2(&self) -> i32 {
-        self.s
-            .lines()
-            .map(|l| required_fuel2(l.parse::<i32>().unwrap()))
-            .sum()
+    fn solve(&self) -> i32 {
+        self.masses.iter().map(|m| required_fuel(*m)).sum()
+    }
+
+    fn solve2(&self) -> i32 {
+        self.masses.iter().map(|m| required_fuel2(*m)).sum()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::{Known, Verdict};
+    use crate::solution::Part;
+    use crate::{answers, input, solution};
+
+    #[test]
+    fn known_answers() {
+        let known = Known::parse(
+            "1 1 591ea9a19d2a7583 34241
+             1 2 591ea9a19d2a7583 51316",
+        )
+        .unwrap();
+        let outcomes = known
+            .check(solution::day(1).unwrap(), "12\n14\n1969\n100756\n")
+            .unwrap();
+        assert!(outcomes.iter().all(|o| o.verdict == Verdict::Pass));
+    }
 
     mod part1 {
         use super::*;
@@ -53,7 +74,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/1")).solve(), 3394032);
+            let Some(input) = input::for_test(1) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(1, Part::One, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve().to_string(), expected);
         }
     }
 
@@ -69,7 +96,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/1")).solve2(), 5088176);
+            let Some(input) = input::for_test(1) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(1, Part::Two, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve2().to_string(), expected);
         }
     }
 }
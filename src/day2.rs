@@ -1,40 +1,36 @@
 use crate::intcode::Intcode;
+use crate::{ParseError, Solution};
 
-pub struct Puzzle<'a> {
-    s: &'a str,
+pub struct Puzzle {
+    intcode: Intcode,
 }
 
-impl<'a> Puzzle<'a> {
-    pub fn new(s: &'a str) -> Self {
-        Puzzle { s }
-    }
-
-    pub fn solve(&self) -> i64 {
-        let mut v: Vec<_> = self.s.split(',').collect();
-        v[1] = "12";
-        v[2] = "2";
+impl Solution<'_> for Puzzle {
+    type Answer1 = i64;
+    type Answer2 = i64;
 
-        let modified = v.join(",");
+    fn new(s: &str) -> Result<Self, ParseError> {
+        Ok(Puzzle {
+            intcode: s.parse()?,
+        })
+    }
 
-        let mut intcode = Intcode::from(modified.as_str());
+    fn solve(&self) -> i64 {
+        let mut intcode = self.intcode.clone();
+        intcode.set_mem(1, 12);
+        intcode.set_mem(2, 2);
         intcode.run();
         intcode.get_mem_range(0, 1)[0]
     }
 
-    pub fn solve2(&self) -> i64 {
-        let mut v: Vec<_> = self
-            .s
-            .trim()
-            .split(',')
-            .map(|s| s.parse::<i64>().unwrap())
-            .collect();
+    fn solve2(&self) -> i64 {
+        let base = &self.intcode;
 
         for noun in 0..=99 {
             for verb in 0..=99 {
-                v[1] = noun;
-                v[2] = verb;
-
-                let mut intcode = Intcode::from(&v[..]);
+                let mut intcode = base.clone();
+                intcode.set_mem(1, noun);
+                intcode.set_mem(2, verb);
                 intcode.run();
                 let first_byte = intcode.get_mem_range(0, 1)[0];
                 if first_byte == 19690720 {
@@ -50,13 +46,21 @@ impl<'a> Puzzle<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solution::Part;
+    use crate::{answers, input};
 
     mod part1 {
         use super::*;
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/2")).solve(), 5290681);
+            let Some(input) = input::for_test(2) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(2, Part::One, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve().to_string(), expected);
         }
     }
 
@@ -65,7 +69,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/2")).solve2(), 5741);
+            let Some(input) = input::for_test(2) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(2, Part::Two, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve2().to_string(), expected);
         }
     }
 }
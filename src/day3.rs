@@ -1,29 +1,36 @@
 use std::collections::{HashMap, HashSet};
 
-pub struct Puzzle<'a> {
-    s: &'a str,
+use crate::parse;
+use crate::{ParseError, Solution};
+
+pub struct Puzzle {
+    wires: [Wire; 2],
 }
 
 struct Wire(HashMap<(i32, i32), usize>);
 
-impl From<&str> for Wire {
-    fn from(s: &str) -> Self {
+impl Wire {
+    /// Parses `line`, a slice of `input`.
+    fn parse(input: &str, line: &str) -> Result<Self, ParseError> {
+        type Stepper = Box<dyn Fn(&mut i32, &mut i32)>;
+
         let mut map = HashMap::new();
 
         let mut cur_x = 0;
         let mut cur_y = 0;
         let mut cur_len = 0;
 
-        for m in s.split(',') {
-            let n = m[1..].parse::<usize>().unwrap();
+        for m in line.split(',') {
+            let mut chars = m.chars();
 
-            let stepper: Box<dyn Fn(&mut i32, &mut i32)> = match m.chars().next().unwrap() {
-                'U' => Box::new(|_, y| *y += 1),
-                'D' => Box::new(|_, y| *y -= 1),
-                'L' => Box::new(|x, _| *x -= 1),
-                'R' => Box::new(|x, _| *x += 1),
-                dir => panic!("unknown direction {dir}"),
+            let stepper: Stepper = match chars.next() {
+                Some('U') => Box::new(|_, y| *y += 1),
+                Some('D') => Box::new(|_, y| *y -= 1),
+                Some('L') => Box::new(|x, _| *x -= 1),
+                Some('R') => Box::new(|x, _| *x += 1),
+                _ => return Err(ParseError::at(input, m, "a move such as R8")),
             };
+            let n: usize = parse::number(input, chars.as_str())?;
 
             for _ in 0..n {
                 stepper(&mut cur_x, &mut cur_y);
@@ -32,11 +39,9 @@ impl From<&str> for Wire {
             }
         }
 
-        Self(map)
+        Ok(Self(map))
     }
-}
 
-impl Wire {
     fn point_set(&self) -> HashSet<(i32, i32)> {
         let mut set = HashSet::new();
 
@@ -48,14 +53,26 @@ impl Wire {
     }
 }
 
-impl<'a> Puzzle<'a> {
-    pub fn new(s: &'a str) -> Self {
-        Puzzle { s }
+impl Solution<'_> for Puzzle {
+    type Answer1 = i32;
+    type Answer2 = i32;
+
+    fn new(s: &str) -> Result<Self, ParseError> {
+        let mut lines = s.lines();
+        let (Some(first), Some(second)) = (lines.next(), lines.next()) else {
+            return Err(ParseError::at(s, &s[s.len()..], "two wires"));
+        };
+        if let Some(extra) = lines.find(|l| !l.trim().is_empty()) {
+            return Err(ParseError::at(s, extra, "only two wires"));
+        }
+
+        Ok(Puzzle {
+            wires: [Wire::parse(s, first)?, Wire::parse(s, second)?],
+        })
     }
 
-    pub fn solve(&self) -> i32 {
-        let wires: Vec<Wire> = self.s.lines().map(Wire::from).collect();
-        assert_eq!(wires.len(), 2);
+    fn solve(&self) -> i32 {
+        let wires = &self.wires;
 
         wires[0]
             .point_set()
@@ -65,9 +82,8 @@ impl<'a> Puzzle<'a> {
             .unwrap()
     }
 
-    pub fn solve2(&self) -> i32 {
-        let wires: Vec<Wire> = self.s.lines().map(Wire::from).collect();
-        assert_eq!(wires.len(), 2);
+    fn solve2(&self) -> i32 {
+        let wires = &self.wires;
 
         wires[0]
             .point_set()
@@ -83,6 +99,48 @@ impl<'a> Puzzle<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::answers::{Known, Verdict};
+    use crate::solution::Part;
+    use crate::{answers, input, solution};
+
+    #[test]
+    fn known_answers() {
+        let known = Known::parse(
+            "3 1 cf28611284cb92d5 6
+             3 2 cf28611284cb92d5 30
+             3 1 c7881d12aa406da3 159
+             3 2 c7881d12aa406da3 610
+             3 1 f1cb61b722d1f154 135
+             3 2 f1cb61b722d1f154 410",
+        )
+        .unwrap();
+        let wires = [
+            "R8,U5,L5,D3\nU7,R6,D4,L4",
+            "R75,D30,R83,U83,L12,D49,R71,U7,L72\nU62,R66,U55,R34,D71,R55,D58,R83",
+            "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51\nU98,R91,D20,R16,D67,R40,U7,R15,U6,R7",
+        ];
+        for input in wires {
+            let outcomes = known.check(solution::day(3).unwrap(), input).unwrap();
+            assert!(outcomes.iter().all(|o| o.verdict == Verdict::Pass));
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let err = |s| Puzzle::new(s).err().unwrap().to_string();
+        assert_eq!(
+            err("R8,U5\nU7,X6,D4"),
+            "line 2, column 4: expected a move such as R8, found 'X6'"
+        );
+        assert_eq!(
+            err("R8,U5\nU7,R6,D"),
+            "line 2, column 8: expected a number, found nothing"
+        );
+        assert_eq!(
+            err("R8,U5"),
+            "line 1, column 6: expected two wires, found nothing"
+        );
+    }
 
     mod part1 {
         use super::*;
@@ -94,6 +152,7 @@ mod tests {
                     "R8,U5,L5,D3\n\
 		     U7,R6,D4,L4\n"
                 )
+                .unwrap()
                 .solve(),
                 6
             );
@@ -102,6 +161,7 @@ mod tests {
                     "R75,D30,R83,U83,L12,D49,R71,U7,L72\n\
 		     U62,R66,U55,R34,D71,R55,D58,R83\n"
                 )
+                .unwrap()
                 .solve(),
                 159
             );
@@ -110,6 +170,7 @@ mod tests {
                     "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51\n\
 		     U98,R91,D20,R16,D67,R40,U7,R15,U6,R7\n"
                 )
+                .unwrap()
                 .solve(),
                 135
             );
@@ -117,7 +178,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/3")).solve(), 2180);
+            let Some(input) = input::for_test(3) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(3, Part::One, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve().to_string(), expected);
         }
     }
 
@@ -131,6 +198,7 @@ mod tests {
                     "R8,U5,L5,D3\n\
 		     U7,R6,D4,L4\n"
                 )
+                .unwrap()
                 .solve2(),
                 30
             );
@@ -139,6 +207,7 @@ mod tests {
                     "R75,D30,R83,U83,L12,D49,R71,U7,L72\n\
 		     U62,R66,U55,R34,D71,R55,D58,R83\n"
                 )
+                .unwrap()
                 .solve2(),
                 610
             );
@@ -147,6 +216,7 @@ mod tests {
                     "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51\n\
 		     U98,R91,D20,R16,D67,R40,U7,R15,U6,R7\n"
                 )
+                .unwrap()
                 .solve2(),
                 410
             );
@@ -154,7 +224,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/3")).solve2(), 112316);
+            let Some(input) = input::for_test(3) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(3, Part::Two, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve2().to_string(), expected);
         }
     }
 }
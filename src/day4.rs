@@ -1,5 +1,9 @@
-pub struct Puzzle<'a> {
-    s: &'a str,
+use crate::parse;
+use crate::{ParseError, Solution};
+
+pub struct Puzzle {
+    from: i32,
+    to: i32,
 }
 
 fn into_digits(mut n: i32) -> [i32; 6] {
@@ -59,31 +63,51 @@ fn valid_password2(n: i32) -> bool {
     }
 }
 
-impl<'a> Puzzle<'a> {
-    pub fn new(s: &'a str) -> Self {
-        Puzzle { s }
-    }
+impl Solution<'_> for Puzzle {
+    type Answer1 = usize;
+    type Answer2 = usize;
 
-    pub fn solve(&self) -> usize {
-        let parts: Vec<_> = self.s.split('-').collect();
-        let from = parts[0].parse::<i32>().unwrap();
-        let to = parts[1].parse::<i32>().unwrap();
+    fn new(s: &str) -> Result<Self, ParseError> {
+        let range = s.trim();
+        let Some((from, to)) = range.split_once('-') else {
+            return Err(ParseError::at(s, range, "a range such as 123456-654321"));
+        };
 
-        (from..=to).filter(|n| valid_password(*n)).count()
+        Ok(Puzzle {
+            from: parse::number(s, from)?,
+            to: parse::number(s, to)?,
+        })
     }
 
-    pub fn solve2(&self) -> usize {
-        let parts: Vec<_> = self.s.split('-').collect();
-        let from = parts[0].parse::<i32>().unwrap();
-        let to = parts[1].parse::<i32>().unwrap();
+    fn solve(&self) -> usize {
+        (self.from..=self.to).filter(|n| valid_password(*n)).count()
+    }
 
-        (from..=to).filter(|n| valid_password2(*n)).count()
+    fn solve2(&self) -> usize {
+        (self.from..=self.to)
+            .filter(|n| valid_password2(*n))
+            .count()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solution::Part;
+    use crate::{answers, input};
+
+    #[test]
+    fn malformed() {
+        let err = |s| Puzzle::new(s).err().unwrap().to_string();
+        assert_eq!(
+            err("123456"),
+            "line 1, column 1: expected a range such as 123456-654321, found '123456'"
+        );
+        assert_eq!(
+            err("123456-65x321\n"),
+            "line 1, column 8: expected a number, found '65x321'"
+        );
+    }
 
     mod part1 {
         use super::*;
@@ -93,7 +117,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/4")).solve(), 1864);
+            let Some(input) = input::for_test(4) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(4, Part::One, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve().to_string(), expected);
         }
     }
 
@@ -105,7 +135,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/4")).solve2(), 1258);
+            let Some(input) = input::for_test(4) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(4, Part::Two, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve2().to_string(), expected);
         }
     }
 }
@@ -1,16 +1,22 @@
 use crate::intcode::Intcode;
+use crate::{ParseError, Solution};
 
-pub struct Puzzle<'a> {
-    s: &'a str,
+pub struct Puzzle {
+    intcode: Intcode,
 }
 
-impl<'a> Puzzle<'a> {
-    pub fn new(s: &'a str) -> Self {
-        Puzzle { s }
+impl Solution<'_> for Puzzle {
+    type Answer1 = i64;
+    type Answer2 = i64;
+
+    fn new(s: &str) -> Result<Self, ParseError> {
+        Ok(Puzzle {
+            intcode: s.parse()?,
+        })
     }
 
-    pub fn solve(&self) -> i64 {
-        let mut intcode = Intcode::from(self.s);
+    fn solve(&self) -> i64 {
+        let mut intcode = self.intcode.clone();
         intcode.input(1);
         intcode.run();
 
@@ -23,8 +29,8 @@ impl<'a> Puzzle<'a> {
         }
     }
 
-    pub fn solve2(&self) -> i64 {
-        let mut intcode = Intcode::from(self.s);
+    fn solve2(&self) -> i64 {
+        let mut intcode = self.intcode.clone();
         intcode.input(5);
         intcode.run();
         intcode.output().unwrap()
@@ -34,13 +40,21 @@ impl<'a> Puzzle<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solution::Part;
+    use crate::{answers, input};
 
     mod part1 {
         use super::*;
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/5")).solve(), 8332629);
+            let Some(input) = input::for_test(5) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(5, Part::One, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve().to_string(), expected);
         }
     }
 
@@ -49,7 +63,13 @@ mod tests {
 
         #[test]
         fn input() {
-            assert_eq!(Puzzle::new(include_str!("../input/5")).solve2(), 8805067);
+            let Some(input) = input::for_test(5) else {
+                return;
+            };
+            let Some(expected) = answers::for_test(5, Part::Two, &input) else {
+                return;
+            };
+            assert_eq!(Puzzle::new(&input).unwrap().solve2().to_string(), expected);
         }
     }
 }
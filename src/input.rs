@@ -0,0 +1,60 @@
+//! Finding puzzle inputs.
+//!
+//! Inputs are personal to each Advent of Code account and are not part of
+//! the repository. They are read at run time from `input/N`, or from the
+//! directory named by `AOC_INPUT_DIR` if it is set.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const DIR_VAR: &str = "AOC_INPUT_DIR";
+
+pub fn dir() -> PathBuf {
+    env::var_os(DIR_VAR).map_or(PathBuf::from("input"), PathBuf::from)
+}
+
+pub fn path(day: u32) -> PathBuf {
+    dir().join(day.to_string())
+}
+
+/// Reads an input file, without the trailing newline.
+pub fn read(path: impl AsRef<Path>) -> io::Result<String> {
+    let mut s = fs::read_to_string(path)?;
+    s.truncate(s.trim_end().len());
+    Ok(s)
+}
+
+/// Reads the input for `day`, or `None` if there is no input file.
+pub fn load(day: u32) -> io::Result<Option<String>> {
+    load_from(&dir(), day)
+}
+
+/// Like [`load`], but from the given directory.
+pub fn load_from(dir: &Path, day: u32) -> io::Result<Option<String>> {
+    match read(dir.join(day.to_string())) {
+        Ok(s) => Ok(Some(s)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the input for `day` in a test, or says the test is skipped.
+///
+/// The message goes straight to stderr so the test harness does not
+/// capture it.
+#[cfg(test)]
+pub(crate) fn for_test(day: u32) -> Option<String> {
+    use std::io::Write;
+
+    let input = load(day).unwrap();
+    if input.is_none() {
+        let _ = writeln!(
+            io::stderr(),
+            "skipping day {day} input test: {} not found (set {DIR_VAR} to change where inputs are read)",
+            path(day).display()
+        );
+    }
+    input
+}
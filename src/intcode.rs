@@ -1,17 +1,45 @@
-use std::collections::{HashMap, VecDeque};
-
-pub struct Intcode {
-    memory: Memory,
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::parse::{self, ParseError};
+
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod debug;
+pub mod devices;
+pub mod disasm;
+#[cfg(test)]
+mod fixtures;
+pub mod hook;
+mod memory;
+pub mod network;
+pub mod nic;
+pub mod play;
+pub mod profile;
+pub mod state;
+pub mod trace;
+pub mod transpile;
+
+pub use devices::{InputSource, OutputSink};
+pub use hook::Hook;
+pub use memory::{DenseMemory, Memory, SparseMemory};
+
+#[derive(Clone)]
+pub struct Intcode<M = DenseMemory> {
+    memory: M,
     ip: usize,
     rb: isize,
     input: VecDeque<i64>,
     output: VecDeque<i64>,
+    cycles: u64,
 }
 
-struct Memory(HashMap<usize, i64>);
-
-#[derive(PartialEq, Eq)]
-enum Opcode {
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub enum Opcode {
     Add = 1,
     Multiply = 2,
     Input = 3,
@@ -24,129 +52,278 @@ enum Opcode {
     Halt = 99,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum ParameterMode {
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum ParameterMode {
     Position = 0,
     Immediate = 1,
     Relative = 2,
 }
 
+/// Everything needed to put a machine back exactly where it was: memory,
+/// instruction pointer, relative base and both I/O queues.
+#[derive(Clone, Debug)]
+pub struct Snapshot<M = DenseMemory> {
+    memory: M,
+    ip: usize,
+    rb: isize,
+    input: VecDeque<i64>,
+    output: VecDeque<i64>,
+    cycles: u64,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum RunResult {
     Ok,
     Halted,
     BlockedOnInput,
+    /// A [`Intcode::run_for`] or [`Intcode::run_until`] budget ran out
+    /// before the program halted or needed input.
+    BudgetExhausted,
 }
 
-struct Instruction {
+/// How many instructions [`Intcode::run_until`] executes between looks at
+/// the clock.
+const DEADLINE_CHECK: u64 = 1024;
+
+/// A fault raised while executing a program, along with the machine
+/// state at the faulting instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntcodeError {
+    pub ip: usize,
+    pub instruction: i64,
+    pub rb: isize,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownOpcode(i64),
+    UnknownParameterMode { param: usize, mode: i64 },
+    ImmediateWrite { param: usize },
+    NegativeAddress(i64),
+    Overflow,
+}
+
+/// A decoded instruction word.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Instruction {
     opcode: Opcode,
     p_mode: [ParameterMode; 3],
     len: usize,
 }
 
-impl From<i64> for Opcode {
-    fn from(n: i64) -> Self {
+impl TryFrom<i64> for Opcode {
+    type Error = ErrorKind;
+
+    fn try_from(n: i64) -> Result<Self, Self::Error> {
         match n {
-            1 => Opcode::Add,
-            2 => Opcode::Multiply,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::JumpIfTrue,
-            6 => Opcode::JumpIfFalse,
-            7 => Opcode::LessThan,
-            8 => Opcode::Equals,
-            9 => Opcode::RelativeBase,
-            99 => Opcode::Halt,
-            other => panic!("Unknown opcode {other}"),
+            1 => Ok(Opcode::Add),
+            2 => Ok(Opcode::Multiply),
+            3 => Ok(Opcode::Input),
+            4 => Ok(Opcode::Output),
+            5 => Ok(Opcode::JumpIfTrue),
+            6 => Ok(Opcode::JumpIfFalse),
+            7 => Ok(Opcode::LessThan),
+            8 => Ok(Opcode::Equals),
+            9 => Ok(Opcode::RelativeBase),
+            99 => Ok(Opcode::Halt),
+            other => Err(ErrorKind::UnknownOpcode(other)),
         }
     }
 }
 
-impl From<i64> for ParameterMode {
-    fn from(n: i64) -> Self {
+impl Opcode {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Multiply => "mul",
+            Opcode::Input => "in",
+            Opcode::Output => "out",
+            Opcode::JumpIfTrue => "jnz",
+            Opcode::JumpIfFalse => "jz",
+            Opcode::LessThan => "lt",
+            Opcode::Equals => "eq",
+            Opcode::RelativeBase => "arb",
+            Opcode::Halt => "hlt",
+        }
+    }
+
+    pub fn from_mnemonic(s: &str) -> Option<Self> {
+        [
+            Opcode::Add,
+            Opcode::Multiply,
+            Opcode::Input,
+            Opcode::Output,
+            Opcode::JumpIfTrue,
+            Opcode::JumpIfFalse,
+            Opcode::LessThan,
+            Opcode::Equals,
+            Opcode::RelativeBase,
+            Opcode::Halt,
+        ]
+        .into_iter()
+        .find(|op| op.mnemonic() == s)
+    }
+
+    /// Instruction length in words, including the opcode itself.
+    fn len(&self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 4,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 3,
+            Opcode::Input | Opcode::Output | Opcode::RelativeBase => 2,
+            Opcode::Halt => 1,
+        }
+    }
+
+    /// Index of the parameter this opcode writes to, if any.
+    fn write_param(&self) -> Option<usize> {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => Some(2),
+            Opcode::Input => Some(0),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<i64> for ParameterMode {
+    type Error = i64;
+
+    fn try_from(n: i64) -> Result<Self, Self::Error> {
         match n {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            2 => ParameterMode::Relative,
-            other => panic!("Unknown parameter mode {other}"),
+            0 => Ok(ParameterMode::Position),
+            1 => Ok(ParameterMode::Immediate),
+            2 => Ok(ParameterMode::Relative),
+            other => Err(other),
         }
     }
 }
 
-impl From<i64> for Instruction {
-    fn from(n: i64) -> Self {
-        let mut digits = n.to_string();
+impl Instruction {
+    pub fn opcode(&self) -> Opcode {
+        self.opcode
+    }
 
-        while digits.len() < 5 {
-            digits.insert(0, '0');
+    /// Modes of the parameters the instruction actually has.
+    pub fn modes(&self) -> &[ParameterMode] {
+        &self.p_mode[..self.len - 1]
+    }
+}
+
+impl TryFrom<i64> for Instruction {
+    type Error = ErrorKind;
+
+    fn try_from(n: i64) -> Result<Self, Self::Error> {
+        if n < 0 {
+            return Err(ErrorKind::UnknownOpcode(n));
         }
 
-        let opcode = Opcode::from(digits[3..].parse::<i64>().unwrap());
+        // Anything left of the third mode digit would be the mode of a
+        // fourth parameter, which no instruction has.
+        if n >= 100_000 {
+            return Err(ErrorKind::UnknownParameterMode {
+                param: 3,
+                mode: n / 100_000,
+            });
+        }
+
+        let mode = |param: usize, digit: i64| {
+            ParameterMode::try_from(digit)
+                .map_err(|mode| ErrorKind::UnknownParameterMode { param, mode })
+        };
+
+        let opcode = Opcode::try_from(n % 100)?;
         let p_mode = [
-            ParameterMode::from(digits[2..3].parse::<i64>().unwrap()),
-            ParameterMode::from(digits[1..2].parse::<i64>().unwrap()),
-            ParameterMode::from(digits[0..1].parse::<i64>().unwrap()),
+            mode(0, n / 100 % 10)?,
+            mode(1, n / 1_000 % 10)?,
+            mode(2, n / 10_000)?,
         ];
 
-        let len = match opcode {
-            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 4,
-            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 3,
-            Opcode::Input | Opcode::Output | Opcode::RelativeBase => 2,
-            Opcode::Halt => 1,
-        };
+        let len = opcode.len();
 
-        Self {
+        if let Some(param) = opcode.write_param() {
+            if p_mode[param] == ParameterMode::Immediate {
+                return Err(ErrorKind::ImmediateWrite { param });
+            }
+        }
+
+        Ok(Self {
             opcode,
             p_mode,
             len,
-        }
+        })
     }
 }
 
-impl From<&str> for Intcode {
-    fn from(s: &str) -> Self {
-        Self {
-            memory: Memory::from(s),
-            ip: 0,
-            rb: 0,
-            input: VecDeque::new(),
-            output: VecDeque::new(),
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownOpcode(n) => write!(f, "unknown opcode {n}"),
+            ErrorKind::UnknownParameterMode { param, mode } => {
+                write!(f, "unknown mode {mode} for parameter {}", param + 1)
+            }
+            ErrorKind::ImmediateWrite { param } => {
+                write!(f, "write to parameter {} in immediate mode", param + 1)
+            }
+            ErrorKind::NegativeAddress(addr) => write!(f, "negative address {addr}"),
+            ErrorKind::Overflow => write!(f, "arithmetic overflow"),
         }
     }
 }
 
-impl From<&[i64]> for Intcode {
-    fn from(s: &[i64]) -> Self {
-        Self {
-            memory: Memory::from(s),
-            ip: 0,
-            rb: 0,
-            input: VecDeque::new(),
-            output: VecDeque::new(),
-        }
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at ip {} (instruction {}, rb {})",
+            self.kind, self.ip, self.instruction, self.rb
+        )
     }
 }
 
-impl From<&str> for Memory {
-    fn from(s: &str) -> Self {
-        let map = s
+impl std::error::Error for IntcodeError {}
+
+impl FromStr for Intcode {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let program = s
             .trim()
             .split(',')
-            .map(|i| i.parse::<i64>().unwrap())
-            .enumerate()
-            .collect();
+            .map(|i| parse::number(s, i.trim()))
+            .collect::<Result<Vec<i64>, _>>()?;
 
-        Self(map)
+        Ok(Self::from(&program[..]))
+    }
+}
+
+/// Panics if `s` is not a valid program; see the [`FromStr`] impl for a
+/// fallible version.
+impl From<&str> for Intcode {
+    fn from(s: &str) -> Self {
+        s.parse().unwrap_or_else(|e| panic!("invalid program: {e}"))
     }
 }
 
-impl From<&[i64]> for Memory {
+impl From<&[i64]> for Intcode {
     fn from(s: &[i64]) -> Self {
-        Self(s.iter().map(|i| *i).enumerate().collect())
+        Self::with_memory(DenseMemory::from(s))
     }
 }
 
-impl Intcode {
+impl<M: Memory> Intcode<M> {
+    /// Creates a machine over an already loaded memory, e.g.
+    /// `Intcode::with_memory(SparseMemory::from(program))`.
+    pub fn with_memory(memory: M) -> Self {
+        Self {
+            memory,
+            ip: 0,
+            rb: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            cycles: 0,
+        }
+    }
+
     pub fn input(&mut self, input: i64) {
         self.input.push_back(input);
     }
@@ -155,199 +332,491 @@ impl Intcode {
         self.output.pop_front()
     }
 
+    /// Runs until the program halts or needs input.
+    ///
+    /// Panics if the program faults; use [`Intcode::try_run`] to recover
+    /// from bad programs instead.
     pub fn run(&mut self) -> RunResult {
+        match self.try_run() {
+            Ok(result) => result,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    pub fn try_run(&mut self) -> Result<RunResult, IntcodeError> {
+        self.with_queues(|vm, input, output| vm.run_with(input, output))
+    }
+
+    /// Like [`Intcode::run`], but gives up with
+    /// [`RunResult::BudgetExhausted`] after executing `max_steps`
+    /// instructions.
+    pub fn run_for(&mut self, max_steps: u64) -> RunResult {
+        match self.try_run_for(max_steps) {
+            Ok(result) => result,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    pub fn try_run_for(&mut self, max_steps: u64) -> Result<RunResult, IntcodeError> {
+        self.with_queues(|vm, input, output| vm.run_bounded(input, output, Some(max_steps), None))
+    }
+
+    /// Like [`Intcode::run`], but gives up with
+    /// [`RunResult::BudgetExhausted`] once `deadline` has passed. The clock
+    /// is only checked every so many instructions, so the machine may run
+    /// slightly past it.
+    pub fn run_until(&mut self, deadline: Instant) -> RunResult {
+        match self.try_run_until(deadline) {
+            Ok(result) => result,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    pub fn try_run_until(&mut self, deadline: Instant) -> Result<RunResult, IntcodeError> {
+        self.with_queues(|vm, input, output| vm.run_bounded(input, output, None, Some(deadline)))
+    }
+
+    /// Executes a single instruction. On error the machine is left
+    /// untouched, so stepping again reports the same fault.
+    pub fn try_step(&mut self) -> Result<RunResult, IntcodeError> {
+        self.with_queues(|vm, input, output| vm.step_with(input, output))
+    }
+
+    /// Like [`Intcode::try_run`], but reads input from `input` and sends
+    /// output to `output` instead of the machine's own queues.
+    pub fn run_with<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<RunResult, IntcodeError>
+    where
+        I: InputSource + ?Sized,
+        O: OutputSink + ?Sized,
+    {
+        self.run_with_hook(input, output, &mut ())
+    }
+
+    pub fn step_with<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<RunResult, IntcodeError>
+    where
+        I: InputSource + ?Sized,
+        O: OutputSink + ?Sized,
+    {
+        self.step_with_hook(input, output, &mut ())
+    }
+
+    /// Like [`Intcode::try_run`], but reports everything the machine does
+    /// to `hook`.
+    pub fn try_run_hooked<H: Hook + ?Sized>(
+        &mut self,
+        hook: &mut H,
+    ) -> Result<RunResult, IntcodeError> {
+        self.with_queues(|vm, input, output| vm.run_with_hook(input, output, hook))
+    }
+
+    pub fn try_step_hooked<H: Hook + ?Sized>(
+        &mut self,
+        hook: &mut H,
+    ) -> Result<RunResult, IntcodeError> {
+        self.with_queues(|vm, input, output| vm.step_with_hook(input, output, hook))
+    }
+
+    pub fn run_with_hook<I, O, H>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+        hook: &mut H,
+    ) -> Result<RunResult, IntcodeError>
+    where
+        I: InputSource + ?Sized,
+        O: OutputSink + ?Sized,
+        H: Hook + ?Sized,
+    {
+        loop {
+            match self.step_with_hook(input, output, hook)? {
+                RunResult::Ok => continue,
+                other => return Ok(other),
+            }
+        }
+    }
+
+    pub fn step_with_hook<I, O, H>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+        hook: &mut H,
+    ) -> Result<RunResult, IntcodeError>
+    where
+        I: InputSource + ?Sized,
+        O: OutputSink + ?Sized,
+        H: Hook + ?Sized,
+    {
+        let result = self.next(input, output, hook).map_err(|kind| {
+            let error = IntcodeError {
+                ip: self.ip,
+                instruction: self.mem_get_addr(self.ip),
+                rb: self.rb,
+                kind,
+            };
+            hook.fault(&error);
+            error
+        })?;
+
+        if result == RunResult::Ok {
+            self.cycles += 1;
+        }
+        Ok(result)
+    }
+
+    fn run_bounded<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+        max_steps: Option<u64>,
+        deadline: Option<Instant>,
+    ) -> Result<RunResult, IntcodeError>
+    where
+        I: InputSource + ?Sized,
+        O: OutputSink + ?Sized,
+    {
+        let start = self.cycles;
+
         loop {
-            match self.next() {
+            let steps = self.cycles - start;
+            if max_steps.is_some_and(|max| steps >= max) {
+                return Ok(RunResult::BudgetExhausted);
+            }
+            if deadline.is_some_and(|d| steps.is_multiple_of(DEADLINE_CHECK) && Instant::now() >= d)
+            {
+                return Ok(RunResult::BudgetExhausted);
+            }
+
+            match self.step_with(input, output)? {
                 RunResult::Ok => continue,
-                other => return other,
+                other => return Ok(other),
             }
         }
     }
 
+    /// Runs `f` with the machine's own queues acting as its devices.
+    fn with_queues<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self, &mut VecDeque<i64>, &mut VecDeque<i64>) -> T,
+    ) -> T {
+        let mut input = mem::take(&mut self.input);
+        let mut output = mem::take(&mut self.output);
+        let result = f(self, &mut input, &mut output);
+        self.input = input;
+        self.output = output;
+        result
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn rb(&self) -> isize {
+        self.rb
+    }
+
+    /// Number of instructions executed so far. Halting, blocking on input
+    /// and faulting do not count.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Inputs queued but not yet consumed by the program.
+    pub fn pending_input(&self) -> impl Iterator<Item = i64> + '_ {
+        self.input.iter().copied()
+    }
+
+    /// Outputs produced but not yet taken with [`Intcode::output`].
+    pub fn pending_output(&self) -> impl Iterator<Item = i64> + '_ {
+        self.output.iter().copied()
+    }
+
+    pub fn get_mem(&self, addr: usize) -> i64 {
+        self.mem_get_addr(addr)
+    }
+
     pub fn get_mem_range(&self, start: usize, end: usize) -> Vec<i64> {
         (start..end).map(|i| self.mem_get_addr(i)).collect()
     }
 
-    fn next(&mut self) -> RunResult {
-        let ins = self.next_instr();
+    pub fn set_mem(&mut self, addr: usize, val: i64) {
+        self.mem_set(addr, val)
+    }
+
+    /// Disassembles everything from address 0 up to the highest address
+    /// that has been loaded or written.
+    pub fn disassemble(&self) -> Vec<disasm::Line> {
+        disasm::disassemble(&self.get_mem_range(0, self.memory.end()))
+    }
+
+    pub fn snapshot(&self) -> Snapshot<M>
+    where
+        M: Clone,
+    {
+        Snapshot {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            rb: self.rb,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            cycles: self.cycles,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot<M>)
+    where
+        M: Clone,
+    {
+        self.memory.clone_from(&snapshot.memory);
+        self.ip = snapshot.ip;
+        self.rb = snapshot.rb;
+        self.input.clone_from(&snapshot.input);
+        self.output.clone_from(&snapshot.output);
+        self.cycles = snapshot.cycles;
+    }
+
+    fn next<I, O, H>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+        hook: &mut H,
+    ) -> Result<RunResult, ErrorKind>
+    where
+        I: InputSource + ?Sized,
+        O: OutputSink + ?Sized,
+        H: Hook + ?Sized,
+    {
+        let ip = self.ip;
+        let ins = self.next_instr()?;
         match ins.opcode {
             Opcode::Add => {
-                let op1 = self.mem_get(ins.p_mode[0], 1);
-                let op2 = self.mem_get(ins.p_mode[1], 2);
-
-                let dst = match ins.p_mode[2] {
-                    ParameterMode::Position => self.mem_get(ParameterMode::Immediate, 3),
-                    ParameterMode::Immediate => panic!("Unexpected write in immediate mode"),
-                    ParameterMode::Relative => (self.mem_get(ParameterMode::Immediate, 3) as isize
-                        + self.rb)
-                        .try_into()
-                        .unwrap(),
-                };
-
-                self.mem_set(dst as usize, op1 + op2);
+                let op1 = self.mem_read(ins.p_mode[0], 1, hook)?;
+                let op2 = self.mem_read(ins.p_mode[1], 2, hook)?;
+                let dst = self.dst_addr(ins.p_mode[2], 3)?;
+
+                self.mem_write(dst, op1.checked_add(op2).ok_or(ErrorKind::Overflow)?, hook);
                 self.ip += ins.len;
             }
             Opcode::Multiply => {
-                let op1 = self.mem_get(ins.p_mode[0], 1);
-                let op2 = self.mem_get(ins.p_mode[1], 2);
-
-                let dst = match ins.p_mode[2] {
-                    ParameterMode::Position => self.mem_get(ParameterMode::Immediate, 3),
-                    ParameterMode::Immediate => panic!("Unexpected write in immediate mode"),
-                    ParameterMode::Relative => (self.mem_get(ParameterMode::Immediate, 3) as isize
-                        + self.rb)
-                        .try_into()
-                        .unwrap(),
-                };
-
-                self.mem_set(dst as usize, op1 * op2);
+                let op1 = self.mem_read(ins.p_mode[0], 1, hook)?;
+                let op2 = self.mem_read(ins.p_mode[1], 2, hook)?;
+                let dst = self.dst_addr(ins.p_mode[2], 3)?;
+
+                self.mem_write(dst, op1.checked_mul(op2).ok_or(ErrorKind::Overflow)?, hook);
                 self.ip += ins.len;
             }
             Opcode::Input => {
-                match ins.p_mode[0] {
-                    ParameterMode::Position => {
-                        let idx = self.mem_get(ParameterMode::Immediate, 1) as usize;
-
-                        match self.input.pop_front() {
-                            Some(i) => self.mem_set(idx, i),
-                            None => return RunResult::BlockedOnInput,
-                        }
-                    }
-                    ParameterMode::Immediate => panic!("Unexpected input in immediate mode"),
-                    ParameterMode::Relative => {
-                        let idx = self.mem_get(ParameterMode::Immediate, 1) as isize;
-                        match self.input.pop_front() {
-                            Some(i) => self.mem_set((self.rb + idx).try_into().unwrap(), i),
-                            None => return RunResult::BlockedOnInput,
-                        }
+                let dst = self.dst_addr(ins.p_mode[0], 1)?;
+
+                match input.read() {
+                    Some(i) => {
+                        hook.input(i);
+                        self.mem_write(dst, i, hook);
                     }
+                    None => return Ok(RunResult::BlockedOnInput),
                 }
 
                 self.ip += ins.len;
             }
             Opcode::Output => {
-                let op = self.mem_get(ins.p_mode[0], 1);
-                self.output.push_back(op);
+                let op = self.mem_read(ins.p_mode[0], 1, hook)?;
+                hook.output(op);
+                output.write(op);
 
                 self.ip += ins.len;
             }
             Opcode::JumpIfTrue => {
-                let cond = self.mem_get(ins.p_mode[0], 1);
+                let cond = self.mem_read(ins.p_mode[0], 1, hook)?;
 
                 if cond != 0 {
-                    let target = self.mem_get(ins.p_mode[1], 2);
-                    self.ip = target as usize;
+                    let target = self.mem_read(ins.p_mode[1], 2, hook)?;
+                    self.ip = to_addr(target)?;
                 } else {
                     self.ip += ins.len;
                 }
             }
             Opcode::JumpIfFalse => {
-                let cond = self.mem_get(ins.p_mode[0], 1);
+                let cond = self.mem_read(ins.p_mode[0], 1, hook)?;
 
                 if cond == 0 {
-                    let target = self.mem_get(ins.p_mode[1], 2);
-                    self.ip = target as usize;
+                    let target = self.mem_read(ins.p_mode[1], 2, hook)?;
+                    self.ip = to_addr(target)?;
                 } else {
                     self.ip += ins.len;
                 }
             }
             Opcode::LessThan => {
-                let op1 = self.mem_get(ins.p_mode[0], 1);
-                let op2 = self.mem_get(ins.p_mode[1], 2);
-
-                let dst = match ins.p_mode[2] {
-                    ParameterMode::Position => self.mem_get(ParameterMode::Immediate, 3) as usize,
-                    ParameterMode::Immediate => panic!("Unexpected write in immediate mode"),
-                    ParameterMode::Relative => (self.mem_get(ParameterMode::Immediate, 3) as isize
-                        + self.rb)
-                        .try_into()
-                        .unwrap(),
-                };
+                let op1 = self.mem_read(ins.p_mode[0], 1, hook)?;
+                let op2 = self.mem_read(ins.p_mode[1], 2, hook)?;
+                let dst = self.dst_addr(ins.p_mode[2], 3)?;
 
                 if op1 < op2 {
-                    self.mem_set(dst, 1);
+                    self.mem_write(dst, 1, hook);
                 } else {
-                    self.mem_set(dst, 0);
+                    self.mem_write(dst, 0, hook);
                 }
 
                 self.ip += ins.len;
             }
             Opcode::Equals => {
-                let op1 = self.mem_get(ins.p_mode[0], 1);
-                let op2 = self.mem_get(ins.p_mode[1], 2);
-
-                let dst = match ins.p_mode[2] {
-                    ParameterMode::Position => self.mem_get(ParameterMode::Immediate, 3) as usize,
-                    ParameterMode::Immediate => panic!("Unexpected write in immediate mode"),
-                    ParameterMode::Relative => (self.mem_get(ParameterMode::Immediate, 3) as isize
-                        + self.rb)
-                        .try_into()
-                        .unwrap(),
-                };
+                let op1 = self.mem_read(ins.p_mode[0], 1, hook)?;
+                let op2 = self.mem_read(ins.p_mode[1], 2, hook)?;
+                let dst = self.dst_addr(ins.p_mode[2], 3)?;
 
                 if op1 == op2 {
-                    self.mem_set(dst, 1);
+                    self.mem_write(dst, 1, hook);
                 } else {
-                    self.mem_set(dst, 0);
+                    self.mem_write(dst, 0, hook);
                 }
 
                 self.ip += ins.len;
             }
             Opcode::RelativeBase => {
-                let op = self.mem_get(ins.p_mode[0], 1);
-                self.rb += op as isize;
+                let op = self.mem_read(ins.p_mode[0], 1, hook)?;
+                let rb = (self.rb as i64)
+                    .checked_add(op)
+                    .ok_or(ErrorKind::Overflow)? as isize;
+                if H::ENABLED {
+                    hook.relative_base(self.rb, rb);
+                }
+                self.rb = rb;
                 self.ip += ins.len;
             }
-            Opcode::Halt => return RunResult::Halted,
+            Opcode::Halt => return Ok(RunResult::Halted),
+        }
+
+        if H::ENABLED {
+            hook.retire(ip, &ins);
         }
+        Ok(RunResult::Ok)
+    }
+
+    fn next_instr(&self) -> Result<Instruction, ErrorKind> {
+        Instruction::try_from(self.mem_get_addr(self.ip))
+    }
+
+    fn mem_get(&self, mode: ParameterMode, offset: usize) -> Result<i64, ErrorKind> {
+        let n = self.mem_get_addr(self.ip + offset);
 
-        RunResult::Ok
+        match mode {
+            ParameterMode::Position => Ok(self.mem_get_addr(to_addr(n)?)),
+            ParameterMode::Immediate => Ok(n),
+            ParameterMode::Relative => Ok(self.mem_get_addr(relative_addr(&self.rb, n)?)),
+        }
     }
 
-    fn next_instr(&self) -> Instruction {
-        Instruction::from(self.mem_get(ParameterMode::Immediate, 0))
+    /// Like [`Intcode::mem_get`], reporting memory reads and operands to
+    /// `hook`.
+    fn mem_read<H: Hook + ?Sized>(
+        &self,
+        mode: ParameterMode,
+        offset: usize,
+        hook: &mut H,
+    ) -> Result<i64, ErrorKind> {
+        if !H::ENABLED {
+            return self.mem_get(mode, offset);
+        }
+
+        let n = self.mem_get_addr(self.ip + offset);
+        let addr = match mode {
+            ParameterMode::Position => to_addr(n)?,
+            ParameterMode::Immediate => {
+                hook.operand(n);
+                return Ok(n);
+            }
+            ParameterMode::Relative => relative_addr(&self.rb, n)?,
+        };
+        let value = self.mem_get_addr(addr);
+        hook.read(addr, value);
+        hook.operand(value);
+        Ok(value)
     }
 
-    fn mem_get(&self, mode: ParameterMode, offset: usize) -> i64 {
-        self.memory.get(&self.ip, &self.rb, mode, offset)
+    fn mem_write<H: Hook + ?Sized>(&mut self, addr: usize, val: i64, hook: &mut H) {
+        if H::ENABLED {
+            hook.write(addr, self.mem_get_addr(addr), val);
+        }
+        self.mem_set(addr, val)
     }
 
     fn mem_get_addr(&self, addr: usize) -> i64 {
-        self.memory.get_addr(addr)
+        self.memory.get(addr)
     }
 
     fn mem_set(&mut self, addr: usize, val: i64) {
         self.memory.set(addr, val)
     }
-}
 
-impl Memory {
-    fn get(&self, ip: &usize, rb: &isize, mode: ParameterMode, offset: usize) -> i64 {
+    /// Resolves the address a write parameter refers to.
+    fn dst_addr(&self, mode: ParameterMode, offset: usize) -> Result<usize, ErrorKind> {
+        let n = self.mem_get_addr(self.ip + offset);
+
         match mode {
-            ParameterMode::Position => {
-                let pos = self.get_addr(*ip + offset);
-                self.get_addr(pos as usize)
-            }
-            ParameterMode::Immediate => self.get_addr(*ip + offset),
-            ParameterMode::Relative => {
-                let op = self.get_addr(*ip + offset);
-                self.get_addr((*rb + op as isize).try_into().unwrap())
-            }
+            ParameterMode::Position => to_addr(n),
+            ParameterMode::Immediate => Err(ErrorKind::ImmediateWrite { param: offset - 1 }),
+            ParameterMode::Relative => relative_addr(&self.rb, n),
         }
     }
+}
 
-    fn get_addr(&self, addr: usize) -> i64 {
-        match self.0.get(&addr) {
-            None => 0,
-            Some(n) => *n,
+impl<M> From<Snapshot<M>> for Intcode<M> {
+    fn from(snapshot: Snapshot<M>) -> Self {
+        Self {
+            memory: snapshot.memory,
+            ip: snapshot.ip,
+            rb: snapshot.rb,
+            input: snapshot.input,
+            output: snapshot.output,
+            cycles: snapshot.cycles,
         }
     }
+}
 
-    fn set(&mut self, addr: usize, val: i64) {
-        self.0.insert(addr, val);
+impl<M: Memory> Snapshot<M> {
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn rb(&self) -> isize {
+        self.rb
+    }
+
+    pub fn input(&self) -> impl Iterator<Item = i64> + '_ {
+        self.input.iter().copied()
+    }
+
+    pub fn output(&self) -> impl Iterator<Item = i64> + '_ {
+        self.output.iter().copied()
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
     }
 }
 
+fn to_addr(n: i64) -> Result<usize, ErrorKind> {
+    usize::try_from(n).map_err(|_| ErrorKind::NegativeAddress(n))
+}
+
+fn relative_addr(rb: &isize, offset: i64) -> Result<usize, ErrorKind> {
+    to_addr(
+        (*rb as i64)
+            .checked_add(offset)
+            .ok_or(ErrorKind::Overflow)?,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -544,4 +1013,312 @@ mod tests {
             assert_eq!(intcode.output().unwrap(), 1125899906842624);
         }
     }
+
+    mod decode {
+        use super::*;
+
+        type Decoded = Result<(Opcode, [ParameterMode; 3], usize), ErrorKind>;
+
+        fn decode(n: i64) -> Decoded {
+            Instruction::try_from(n).map(|ins| (ins.opcode, ins.p_mode, ins.len))
+        }
+
+        /// The original string-based decoder, kept as a reference.
+        fn decode_via_string(n: i64) -> Decoded {
+            if n < 0 {
+                return Err(ErrorKind::UnknownOpcode(n));
+            }
+
+            let mut digits = n.to_string();
+
+            while digits.len() < 5 {
+                digits.insert(0, '0');
+            }
+
+            if digits.len() > 5 {
+                return Err(ErrorKind::UnknownParameterMode {
+                    param: 3,
+                    mode: n / 100_000,
+                });
+            }
+
+            let mode = |param: usize, digit: &str| {
+                ParameterMode::try_from(digit.parse::<i64>().unwrap())
+                    .map_err(|mode| ErrorKind::UnknownParameterMode { param, mode })
+            };
+
+            let opcode = Opcode::try_from(digits[3..].parse::<i64>().unwrap())?;
+            let p_mode = [
+                mode(0, &digits[2..3])?,
+                mode(1, &digits[1..2])?,
+                mode(2, &digits[0..1])?,
+            ];
+
+            if let Some(param) = opcode.write_param() {
+                if p_mode[param] == ParameterMode::Immediate {
+                    return Err(ErrorKind::ImmediateWrite { param });
+                }
+            }
+
+            Ok((opcode, p_mode, opcode.len()))
+        }
+
+        #[test]
+        fn matches_string_decoding() {
+            for n in (-100..100_100).chain([i64::MIN, i64::MAX, 1_000_001]) {
+                assert_eq!(decode(n), decode_via_string(n), "decoding {n}");
+            }
+        }
+    }
+
+    mod snapshot {
+        use super::*;
+
+        // Reads two numbers and outputs their sum.
+        const ADDER: &str = "3,100,3,101,1,100,101,102,4,102,99";
+
+        #[test]
+        fn restore() {
+            let mut intcode = Intcode::from(ADDER);
+            intcode.input(3);
+            assert_eq!(intcode.run(), RunResult::BlockedOnInput);
+
+            let snapshot = intcode.snapshot();
+            assert_eq!(snapshot.ip(), 2);
+            assert_eq!(snapshot.memory().get(100), 3);
+
+            intcode.input(4);
+            intcode.run();
+            assert_eq!(intcode.output(), Some(7));
+
+            intcode.restore(&snapshot);
+            assert_eq!(intcode.ip(), 2);
+            intcode.input(10);
+            intcode.run();
+            assert_eq!(intcode.output(), Some(13));
+        }
+
+        #[test]
+        fn queues_are_captured() {
+            let mut intcode = Intcode::from("104,1,104,2,3,0,99");
+            intcode.input(5);
+            intcode.input(6);
+            intcode.try_step().unwrap();
+
+            let snapshot = intcode.snapshot();
+            assert_eq!(snapshot.input().collect::<Vec<_>>(), vec![5, 6]);
+            assert_eq!(snapshot.output().collect::<Vec<_>>(), vec![1]);
+
+            intcode.run();
+            assert_eq!(intcode.output(), Some(1));
+            assert_eq!(intcode.output(), Some(2));
+            assert_eq!(intcode.pending_input().collect::<Vec<_>>(), vec![6]);
+
+            let mut restored = Intcode::from(snapshot);
+            assert_eq!(restored.pending_input().collect::<Vec<_>>(), vec![5, 6]);
+            assert_eq!(restored.output(), Some(1));
+            assert_eq!(restored.output(), None);
+        }
+
+        #[test]
+        fn fork() {
+            let mut base = Intcode::with_memory(SparseMemory::from(
+                &[3, 100, 3, 101, 1, 100, 101, 102, 4, 102, 99][..],
+            ));
+            base.input(1);
+            base.run();
+
+            let outputs: Vec<i64> = (0..3)
+                .map(|n| {
+                    let mut fork = base.clone();
+                    fork.input(n);
+                    fork.run();
+                    fork.output().unwrap()
+                })
+                .collect();
+            assert_eq!(outputs, vec![1, 2, 3]);
+            assert_eq!(base.ip(), 2);
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        #[test]
+        fn unknown_opcode() {
+            let mut intcode = Intcode::from("1101,1,2,5,42,0");
+            let err = intcode.try_run().unwrap_err();
+            assert_eq!(
+                err,
+                IntcodeError {
+                    ip: 4,
+                    instruction: 42,
+                    rb: 0,
+                    kind: ErrorKind::UnknownOpcode(42),
+                }
+            );
+            assert_eq!(intcode.get_mem_range(5, 6), vec![3]);
+
+            let mut intcode = Intcode::from("-1");
+            assert_eq!(
+                intcode.try_step().unwrap_err().kind,
+                ErrorKind::UnknownOpcode(-1)
+            );
+        }
+
+        #[test]
+        fn unknown_parameter_mode() {
+            let mut intcode = Intcode::from("109,5,304,0,99");
+            assert_eq!(intcode.try_step(), Ok(RunResult::Ok));
+            let err = intcode.try_step().unwrap_err();
+            assert_eq!(err.ip, 2);
+            assert_eq!(err.rb, 5);
+            assert_eq!(
+                err.kind,
+                ErrorKind::UnknownParameterMode { param: 0, mode: 3 }
+            );
+
+            let mut intcode = Intcode::from("100001,0,0,0,99");
+            assert_eq!(
+                intcode.try_step().unwrap_err().kind,
+                ErrorKind::UnknownParameterMode { param: 3, mode: 1 }
+            );
+        }
+
+        #[test]
+        fn immediate_write() {
+            let mut intcode = Intcode::from("11101,1,2,3,99");
+            assert_eq!(
+                intcode.try_run().unwrap_err().kind,
+                ErrorKind::ImmediateWrite { param: 2 }
+            );
+
+            let mut intcode = Intcode::from("103,0,99");
+            intcode.input(1);
+            assert_eq!(
+                intcode.try_run().unwrap_err().kind,
+                ErrorKind::ImmediateWrite { param: 0 }
+            );
+        }
+
+        #[test]
+        fn negative_address() {
+            let mut intcode = Intcode::from("1,-1,0,0,99");
+            assert_eq!(
+                intcode.try_run().unwrap_err().kind,
+                ErrorKind::NegativeAddress(-1)
+            );
+
+            let mut intcode = Intcode::from("203,-1,99");
+            intcode.input(7);
+            assert_eq!(
+                intcode.try_run().unwrap_err().kind,
+                ErrorKind::NegativeAddress(-1)
+            );
+            // The faulting input was not consumed.
+            assert_eq!(intcode.input.len(), 1);
+
+            let mut intcode = Intcode::from("1105,1,-3");
+            assert_eq!(
+                intcode.try_run().unwrap_err().kind,
+                ErrorKind::NegativeAddress(-3)
+            );
+        }
+
+        #[test]
+        fn overflow() {
+            let mut intcode = Intcode::from("1102,4611686018427387904,2,0,99");
+            assert_eq!(intcode.try_run().unwrap_err().kind, ErrorKind::Overflow);
+        }
+
+        #[test]
+        fn error_is_sticky() {
+            let mut intcode = Intcode::from("42");
+            let first = intcode.try_run().unwrap_err();
+            assert_eq!(intcode.try_run().unwrap_err(), first);
+            assert_eq!(
+                first.to_string(),
+                "unknown opcode 42 at ip 0 (instruction 42, rb 0)"
+            );
+        }
+
+        #[test]
+        #[should_panic(expected = "unknown opcode 42")]
+        fn run_panics() {
+            Intcode::from("42").run();
+        }
+
+        #[test]
+        fn malformed_program() {
+            let parse = |s: &str| s.parse::<Intcode>().map(|vm| vm.get_mem_range(0, 5));
+            assert_eq!(parse("1,0,0,0,99\n"), Ok(vec![1, 0, 0, 0, 99]));
+
+            let err = parse("1,0, x0,0,99").unwrap_err();
+            assert_eq!((err.line, err.column, err.text.as_str()), (1, 6, "x0"));
+
+            let err = parse("1,0,,99").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "line 1, column 5: expected a number, found nothing"
+            );
+        }
+    }
+
+    mod budget {
+        use super::*;
+        use std::time::Duration;
+
+        // Jumps to itself forever.
+        const SPIN: &str = "1105,1,0";
+
+        #[test]
+        fn run_for() {
+            let mut intcode = Intcode::from(SPIN);
+            assert_eq!(intcode.run_for(1000), RunResult::BudgetExhausted);
+            assert_eq!(intcode.cycles(), 1000);
+            assert_eq!(intcode.run_for(500), RunResult::BudgetExhausted);
+            assert_eq!(intcode.cycles(), 1500);
+
+            let mut intcode = Intcode::from("1101,1,2,5,99,0");
+            assert_eq!(intcode.run_for(1), RunResult::BudgetExhausted);
+            assert_eq!(intcode.run_for(1), RunResult::Halted);
+            assert_eq!(intcode.cycles(), 1);
+        }
+
+        #[test]
+        fn run_until() {
+            let mut intcode = Intcode::from(SPIN);
+            let deadline = Instant::now() + Duration::from_millis(10);
+            assert_eq!(intcode.run_until(deadline), RunResult::BudgetExhausted);
+            assert!(Instant::now() >= deadline);
+            assert!(intcode.cycles() > 0);
+        }
+
+        #[test]
+        fn only_executed_instructions_count() {
+            let mut intcode = Intcode::from("3,0,4,0,99");
+            assert_eq!(intcode.run(), RunResult::BlockedOnInput);
+            assert_eq!(intcode.cycles(), 0);
+
+            intcode.input(7);
+            assert_eq!(intcode.run(), RunResult::Halted);
+            assert_eq!(intcode.run(), RunResult::Halted);
+            assert_eq!(intcode.cycles(), 2);
+
+            let mut intcode = Intcode::from("42");
+            intcode.try_run().unwrap_err();
+            assert_eq!(intcode.cycles(), 0);
+        }
+
+        #[test]
+        fn restore_rewinds_cycles() {
+            let mut intcode = Intcode::from(SPIN);
+            intcode.run_for(10);
+            let snapshot = intcode.snapshot();
+            intcode.run_for(10);
+            intcode.restore(&snapshot);
+            assert_eq!(intcode.cycles(), 10);
+            assert_eq!(Intcode::from(snapshot).cycles(), 10);
+        }
+    }
 }
@@ -0,0 +1,178 @@
+//! Talking to programs that speak ASCII.
+//!
+//! Such programs print text one character per output value and read
+//! commands as newline-terminated lines. Many also finish by printing a
+//! single value outside the ASCII range, such as a puzzle answer, which
+//! [`Collector`] keeps apart from the text.
+
+use super::{Intcode, IntcodeError, Memory, OutputSink, RunResult};
+
+/// Splits output into ASCII text and values outside the ASCII range.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Collector {
+    text: String,
+    value: Option<i64>,
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The last value seen outside the ASCII range.
+    pub fn value(&self) -> Option<i64> {
+        self.value
+    }
+
+    pub fn into_parts(self) -> (String, Option<i64>) {
+        (self.text, self.value)
+    }
+}
+
+impl OutputSink for Collector {
+    fn write(&mut self, value: i64) {
+        match ascii(value) {
+            Some(c) => self.text.push(c),
+            None => self.value = Some(value),
+        }
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    /// Queues `line` as input, one character at a time, followed by a
+    /// newline unless it already ends in one.
+    pub fn input_line(&mut self, line: &str) {
+        for c in line.chars() {
+            self.input(c as i64);
+        }
+        if !line.ends_with('\n') {
+            self.input('\n' as i64);
+        }
+    }
+
+    /// Takes the next complete line of ASCII output, without its newline.
+    /// Returns `None`, taking nothing, if no full line is pending or it
+    /// contains a value outside the ASCII range.
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+
+        for value in self.pending_output() {
+            match ascii(value)? {
+                '\n' => break,
+                c => line.push(c),
+            }
+        }
+
+        if self.output.get(line.len()) != Some(&('\n' as i64)) {
+            return None;
+        }
+        self.output.drain(..=line.len());
+        Some(line)
+    }
+
+    /// Runs the machine until its output ends with `prompt`, then takes
+    /// and returns all pending output as text, prompt included.
+    ///
+    /// Returns `None`, taking nothing, if the machine halts or blocks on
+    /// input before printing the prompt, or printed a value outside the
+    /// ASCII range.
+    pub fn read_until_prompt(&mut self, prompt: &str) -> Result<Option<String>, IntcodeError> {
+        let prompt: Vec<i64> = prompt.chars().map(|c| c as i64).collect();
+
+        loop {
+            if self.output.len() >= prompt.len()
+                && self
+                    .output
+                    .range(self.output.len() - prompt.len()..)
+                    .eq(&prompt)
+            {
+                let text: Option<String> = self.pending_output().map(ascii).collect();
+                if text.is_some() {
+                    self.output.clear();
+                }
+                return Ok(text);
+            }
+
+            if self.try_step()? != RunResult::Ok {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Takes all pending output, split into text and any non-ASCII value.
+    pub fn take_ascii(&mut self) -> Collector {
+        let mut collector = Collector::new();
+        for value in self.output.drain(..) {
+            collector.write(value);
+        }
+        collector
+    }
+}
+
+fn ascii(value: i64) -> Option<char> {
+    u8::try_from(value)
+        .ok()
+        .filter(u8::is_ascii)
+        .map(char::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::devices::input_iter;
+    use crate::intcode::fixtures::echo;
+
+    #[test]
+    fn lines() {
+        let mut intcode = echo();
+        intcode.input_line("ab");
+        intcode.input_line("cd\n");
+        assert_eq!(intcode.run(), RunResult::BlockedOnInput);
+
+        assert_eq!(intcode.read_line(), Some("? ab".to_string()));
+        assert_eq!(intcode.read_line(), Some("? cd".to_string()));
+        assert_eq!(intcode.read_line(), None);
+        assert_eq!(intcode.take_ascii().text(), "? ");
+    }
+
+    #[test]
+    fn prompts() {
+        let mut intcode = echo();
+        assert_eq!(intcode.read_until_prompt("? "), Ok(Some("? ".to_string())));
+
+        intcode.input_line("hello");
+        assert_eq!(
+            intcode.read_until_prompt("? "),
+            Ok(Some("hello\n? ".to_string()))
+        );
+
+        // Blocks on input before the prompt comes up again.
+        intcode.input('x' as i64);
+        assert_eq!(intcode.read_until_prompt("? "), Ok(None));
+        assert_eq!(intcode.pending_output().collect::<Vec<_>>(), vec![120]);
+    }
+
+    #[test]
+    fn trailing_value() {
+        let mut intcode = echo();
+        intcode.input_line("hiq");
+        assert_eq!(intcode.run(), RunResult::Halted);
+
+        // Neither a line nor a prompt can contain the number.
+        assert_eq!(intcode.read_line(), None);
+        assert_eq!(intcode.read_until_prompt("? "), Ok(None));
+
+        let collector = intcode.take_ascii();
+        assert_eq!(collector.into_parts(), ("? hi".to_string(), Some(1000000)));
+
+        let mut collector = Collector::new();
+        let mut input = input_iter("q\n".chars().map(|c| c as i64));
+        echo().run_with(&mut input, &mut collector).unwrap();
+        assert_eq!(collector.text(), "? ");
+        assert_eq!(collector.value(), Some(1000000));
+    }
+}
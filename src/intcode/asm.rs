@@ -0,0 +1,384 @@
+//! A small assembler for Intcode.
+//!
+//! Source is line oriented. Each line holds an optional `label:`, then an
+//! optional instruction or directive, then an optional `; comment`:
+//!
+//! ```text
+//! loop:   in [rb+1]
+//!         mul [rb+1], #2, [out]
+//!         out [out]
+//!         jnz #1, #loop
+//! out:    .data 0
+//! buf:    .fill 8, -1
+//! ```
+//!
+//! Operands spell out their parameter mode: `[addr]` is position mode,
+//! `#value` is immediate mode and `[rb+offset]` (or `[rb-offset]`) is
+//! relative mode. Addresses and values may be numbers, labels, or a label
+//! plus or minus a number, so code and data can be moved around freely.
+//! The mnemonics match those printed by [`super::disasm`].
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{Opcode, ParameterMode};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// A value that may refer to a label, resolved once every label's address
+/// is known.
+struct Expr {
+    label: Option<String>,
+    offset: i64,
+}
+
+struct Word {
+    line: usize,
+    value: Expr,
+}
+
+/// Longest program [`assemble`] produces, so a stray `.fill` count fails
+/// instead of exhausting memory.
+const MAX_WORDS: usize = 1 << 24;
+
+pub fn assemble(src: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut words: Vec<Word> = Vec::new();
+
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |message: String| AsmError {
+            line: line_no,
+            message,
+        };
+
+        let mut rest = match line.find(';') {
+            Some(idx) => &line[..idx],
+            None => line,
+        }
+        .trim();
+
+        if let Some((label, stmt)) = rest.split_once(':') {
+            let label = label.trim();
+            if !is_ident(label) {
+                return Err(err(format!("invalid label '{label}'")));
+            }
+            if labels.insert(label.to_string(), words.len()).is_some() {
+                return Err(err(format!("duplicate label '{label}'")));
+            }
+            rest = stmt.trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (name, args) = match rest.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, split_args(args)),
+            None => (rest, Vec::new()),
+        };
+
+        let word = |value: Expr| Word {
+            line: line_no,
+            value,
+        };
+
+        match name {
+            ".data" => {
+                if args.is_empty() {
+                    return Err(err(".data needs at least one value".to_string()));
+                }
+                for arg in args {
+                    words.push(word(parse_expr(arg).map_err(err)?));
+                }
+            }
+            ".fill" => {
+                let [count, value] = args[..] else {
+                    return Err(err(".fill takes a count and a value".to_string()));
+                };
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| err(format!("invalid .fill count '{count}'")))?;
+                if count > MAX_WORDS.saturating_sub(words.len()) {
+                    return Err(err(format!(
+                        ".fill count {count} makes the program longer than {MAX_WORDS} words"
+                    )));
+                }
+                let value = parse_expr(value).map_err(err)?;
+                for _ in 0..count {
+                    words.push(word(Expr {
+                        label: value.label.clone(),
+                        offset: value.offset,
+                    }));
+                }
+            }
+            mnemonic => {
+                let opcode = Opcode::from_mnemonic(mnemonic)
+                    .ok_or_else(|| err(format!("unknown mnemonic '{mnemonic}'")))?;
+
+                if args.len() != opcode.len() - 1 {
+                    return Err(err(format!(
+                        "{mnemonic} takes {} operand(s), found {}",
+                        opcode.len() - 1,
+                        args.len()
+                    )));
+                }
+
+                let mut code = opcode as i64;
+                let mut operands = Vec::with_capacity(args.len());
+
+                for (param, (arg, scale)) in args.iter().zip([100, 1000, 10000]).enumerate() {
+                    let (mode, value) = parse_operand(arg).map_err(err)?;
+                    if mode == ParameterMode::Immediate && opcode.write_param() == Some(param) {
+                        return Err(err(format!(
+                            "operand {} of {mnemonic} is written to and cannot be immediate",
+                            param + 1
+                        )));
+                    }
+                    code += mode as i64 * scale;
+                    operands.push(value);
+                }
+
+                words.push(word(Expr {
+                    label: None,
+                    offset: code,
+                }));
+                words.extend(operands.into_iter().map(word));
+            }
+        }
+    }
+
+    words
+        .into_iter()
+        .map(|w| match w.value.label {
+            None => Ok(w.value.offset),
+            Some(label) => match labels.get(&label) {
+                Some(addr) => (*addr as i64)
+                    .checked_add(w.value.offset)
+                    .ok_or_else(|| AsmError {
+                        line: w.line,
+                        message: format!("'{label}{:+}' is out of range", w.value.offset),
+                    }),
+                None => Err(AsmError {
+                    line: w.line,
+                    message: format!("undefined label '{label}'"),
+                }),
+            },
+        })
+        .collect()
+}
+
+fn split_args(args: &str) -> Vec<&str> {
+    args.split(',').map(|a| a.trim()).collect()
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && s != "rb"
+}
+
+fn parse_operand(s: &str) -> Result<(ParameterMode, Expr), String> {
+    if let Some(value) = s.strip_prefix('#') {
+        return Ok((ParameterMode::Immediate, parse_expr(value)?));
+    }
+
+    let inner = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| format!("invalid operand '{s}', expected #value, [addr] or [rb+offset]"))?
+        .trim();
+
+    match inner.strip_prefix("rb") {
+        Some("") => Ok((
+            ParameterMode::Relative,
+            Expr {
+                label: None,
+                offset: 0,
+            },
+        )),
+        Some(offset) if offset.starts_with('+') => {
+            Ok((ParameterMode::Relative, parse_expr(&offset[1..])?))
+        }
+        Some(offset) if offset.starts_with('-') => match parse_expr(&offset[1..])? {
+            Expr {
+                label: None,
+                offset,
+            } => Ok((
+                ParameterMode::Relative,
+                Expr {
+                    label: None,
+                    offset: offset
+                        .checked_neg()
+                        .ok_or_else(|| format!("offset out of range in '{s}'"))?,
+                },
+            )),
+            _ => Err(format!("cannot negate a label in '{s}'")),
+        },
+        _ => Ok((ParameterMode::Position, parse_expr(inner)?)),
+    }
+}
+
+fn parse_expr(s: &str) -> Result<Expr, String> {
+    let s = s.trim();
+
+    if let Ok(n) = s.parse::<i64>() {
+        return Ok(Expr {
+            label: None,
+            offset: n,
+        });
+    }
+
+    let (label, offset) = match s.rfind(['+', '-']) {
+        Some(idx) => {
+            let offset = s[idx..]
+                .parse::<i64>()
+                .map_err(|_| format!("invalid offset in '{s}'"))?;
+            (s[..idx].trim(), offset)
+        }
+        None => (s, 0),
+    };
+
+    if !is_ident(label) {
+        return Err(format!("invalid value '{s}'"));
+    }
+
+    Ok(Expr {
+        label: Some(label.to_string()),
+        offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{disasm, Intcode};
+
+    #[test]
+    fn day5_examples() {
+        let program = assemble(
+            "       in [n]
+                    eq [n], [eight], [n]
+                    out [n]
+                    hlt
+             n:     .data -1
+             eight: .data 8",
+        )
+        .unwrap();
+        assert_eq!(program, vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+
+        let program = assemble(
+            "       in [n]
+                    jz [n], [target]
+                    add [result], [one], [result]
+             zero:  out [result]
+                    hlt
+             n:      .data -1
+             result: .data 0
+             one:    .data 1
+             ; jz reads its target through this cell
+             target: .data zero",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9]
+        );
+
+        let mut intcode = Intcode::from(&program[..]);
+        intcode.input(42);
+        intcode.run();
+        assert_eq!(intcode.output(), Some(1));
+    }
+
+    #[test]
+    fn labels_and_directives() {
+        let program = assemble(
+            "start: arb #buf          ; point rb at the buffer
+                    in [rb+1]
+                    out [rb-1]
+                    jnz #1, #start+2
+             buf:   .fill 3, 7
+                    .data buf, buf-1, -2",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            vec![109, 9, 203, 1, 204, -1, 1105, 1, 2, 7, 7, 7, 9, 8, -2]
+        );
+    }
+
+    #[test]
+    fn disassembly_round_trip() {
+        let program = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99, 22201, -3, 4, 5, 209, -7,
+        ];
+
+        let src = disasm::disassemble(&program)
+            .iter()
+            .map(|line| line.item.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert_eq!(assemble(&src).unwrap(), program);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |src| assemble(src).unwrap_err();
+
+        assert_eq!(
+            err("hlt\nfoo #1"),
+            AsmError {
+                line: 2,
+                message: "unknown mnemonic 'foo'".to_string()
+            }
+        );
+        assert_eq!(err("add #1, #2").line, 1);
+        assert_eq!(
+            err("add #1, #2, #3").message,
+            "operand 3 of add is written to and cannot be immediate"
+        );
+        assert_eq!(err("x: hlt\nx: hlt").message, "duplicate label 'x'");
+        assert_eq!(err("\n\nout [nowhere]").line, 3);
+        assert_eq!(err("out [nowhere]").message, "undefined label 'nowhere'");
+        assert_eq!(err("out 5").line, 1);
+        assert_eq!(err(".fill 3").line, 1);
+        assert_eq!(
+            err("out [rb-x]").message,
+            "cannot negate a label in '[rb-x]'"
+        );
+        assert_eq!(err("rb: hlt").message, "invalid label 'rb'");
+        assert_eq!(
+            err("out [rb--9223372036854775808]").message,
+            "offset out of range in '[rb--9223372036854775808]'"
+        );
+        assert_eq!(
+            err("out #x+9223372036854775807\nx: hlt").message,
+            "'x+9223372036854775807' is out of range"
+        );
+        assert_eq!(
+            err(".fill 18446744073709551615, 0").message,
+            ".fill count 18446744073709551615 makes the program longer than 16777216 words"
+        );
+        assert_eq!(
+            err("hlt\n\n.data").to_string(),
+            "line 3: .data needs at least one value"
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! Control flow analysis without running the program.
+//!
+//! [`Cfg::new`] follows the program from address 0, splitting the code it
+//! reaches into basic blocks. Jumps are followed when their target is an
+//! immediate operand; anything else is a [`Target::Dynamic`] jump whose
+//! destinations are unknown. A jump whose condition is an immediate is
+//! either always taken or never taken, and is treated as such. A jump
+//! into the middle of an instruction starts a block of its own, decoded
+//! from the target onwards, that may overlap others.
+//!
+//! The analysis trusts the program as loaded. Instructions that write
+//! into reachable code with a position-mode operand are listed by
+//! [`Cfg::self_writes`], since they may change what actually runs.
+//! Relative-mode writes can land anywhere and are not tracked.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::disasm::{self, Item, Line, Operand};
+use super::{Intcode, Memory, Opcode};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Target {
+    Static(i64),
+    Dynamic,
+}
+
+/// How control leaves a basic block.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Exit {
+    /// Runs on into the code at this address.
+    Next(usize),
+    /// A conditional jump, falling through to `next` when not taken.
+    Branch {
+        target: Target,
+        next: usize,
+    },
+    /// A jump that is always taken.
+    Jump(Target),
+    Halt,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    /// One past the last word of the block.
+    pub end: usize,
+    pub lines: Vec<Line>,
+    pub exit: Exit,
+}
+
+/// An instruction at `writer` that writes to `target`, which is part of
+/// the reachable instruction at `instruction`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct SelfWrite {
+    pub writer: usize,
+    pub target: usize,
+    pub instruction: usize,
+}
+
+#[derive(Debug)]
+pub struct Cfg {
+    blocks: BTreeMap<usize, Block>,
+    invalid: BTreeSet<usize>,
+    self_writes: Vec<SelfWrite>,
+}
+
+impl Exit {
+    /// The statically known addresses control can go to next.
+    pub fn successors(&self) -> Vec<usize> {
+        let target = |t: &Target| match t {
+            Target::Static(t) => usize::try_from(*t).ok(),
+            Target::Dynamic => None,
+        };
+
+        match self {
+            Exit::Next(next) => vec![*next],
+            Exit::Branch { target: t, next } => target(t).into_iter().chain([*next]).collect(),
+            Exit::Jump(t) => target(t).into_iter().collect(),
+            Exit::Halt => Vec::new(),
+        }
+    }
+}
+
+impl Cfg {
+    pub fn new(program: &[i64]) -> Self {
+        let mut lines = BTreeMap::new();
+        let mut leaders = BTreeSet::from([0]);
+        let mut invalid = BTreeSet::new();
+        let mut work = vec![0];
+
+        while let Some(addr) = work.pop() {
+            if lines.contains_key(&addr) || invalid.contains(&addr) {
+                continue;
+            }
+            let line = match disasm::decode(program, addr) {
+                Some(line) if !matches!(line.item, Item::Data(_)) => line,
+                _ => {
+                    invalid.insert(addr);
+                    continue;
+                }
+            };
+
+            match exit(&line) {
+                Some(exit) => {
+                    let next = exit.successors();
+                    leaders.extend(&next);
+                    work.extend(next);
+                }
+                None => work.push(addr + line.words.len()),
+            }
+            lines.insert(addr, line);
+        }
+
+        // A jump into the middle of an instruction decodes the words from
+        // there a second way. Where two such decodes run into the same
+        // address, a block has to start there so that neither runs on
+        // into code the other has claimed.
+        let mut falls_into = BTreeMap::<usize, usize>::new();
+        for line in lines.values().filter(|line| exit(line).is_none()) {
+            *falls_into.entry(line.addr + line.words.len()).or_default() += 1;
+        }
+        leaders.extend(
+            falls_into
+                .into_iter()
+                .filter(|&(_, n)| n > 1)
+                .map(|(addr, _)| addr),
+        );
+
+        let mut blocks = BTreeMap::new();
+        for &start in &leaders {
+            let mut block = Vec::new();
+            let mut addr = start;
+
+            let exit = loop {
+                let Some(line) = lines.remove(&addr) else {
+                    break Exit::Next(addr);
+                };
+                let exit = exit(&line);
+                addr += line.words.len();
+                block.push(line);
+
+                match exit {
+                    Some(exit) => break exit,
+                    None if leaders.contains(&addr) => break Exit::Next(addr),
+                    None => {}
+                }
+            };
+
+            if !block.is_empty() {
+                blocks.insert(
+                    start,
+                    Block {
+                        start,
+                        end: addr,
+                        lines: block,
+                        exit,
+                    },
+                );
+            }
+        }
+
+        let mut cfg = Cfg {
+            blocks,
+            invalid,
+            self_writes: Vec::new(),
+        };
+        cfg.self_writes = cfg
+            .blocks
+            .values()
+            .flat_map(|block| &block.lines)
+            .filter_map(|line| {
+                let target = usize::try_from(written(line)?).ok()?;
+                let instruction = cfg.instruction_at(target)?;
+                Some(SelfWrite {
+                    writer: line.addr,
+                    target,
+                    instruction,
+                })
+            })
+            .collect();
+        cfg
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    /// The block starting at `addr`.
+    pub fn block(&self, addr: usize) -> Option<&Block> {
+        self.blocks.get(&addr)
+    }
+
+    /// The block containing `addr`.
+    pub fn block_at(&self, addr: usize) -> Option<&Block> {
+        self.blocks
+            .range(..=addr)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| addr < block.end)
+    }
+
+    /// Whether `addr` is part of a reachable instruction.
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.instruction_at(addr).is_some()
+    }
+
+    /// Addresses control can reach that do not hold a valid instruction.
+    pub fn invalid(&self) -> impl Iterator<Item = usize> + '_ {
+        self.invalid.iter().copied()
+    }
+
+    pub fn self_writes(&self) -> &[SelfWrite] {
+        &self.self_writes
+    }
+
+    fn instruction_at(&self, addr: usize) -> Option<usize> {
+        self.block_at(addr)?
+            .lines
+            .iter()
+            .find(|line| line.addr <= addr && addr < line.addr + line.words.len())
+            .map(|line| line.addr)
+    }
+
+    /// Renders the graph in Graphviz DOT format. Blocks holding code that
+    /// is written to are drawn in red, with a dashed edge from the writer.
+    pub fn dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph intcode {\n");
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        let node = |t: &Target| match t {
+            Target::Static(t) => format!("\"{t}\""),
+            Target::Dynamic => "\"?\"".to_string(),
+        };
+        let mut dynamic = false;
+        let mut bad = BTreeSet::new();
+
+        for block in self.blocks() {
+            let mut label = String::new();
+            for line in &block.lines {
+                write!(label, "{}: {}\\l", line.addr, line.item).unwrap();
+            }
+            let mut attrs = format!("label=\"{label}\"");
+            if block.exit == Exit::Halt {
+                attrs.push_str(", peripheries=2");
+            }
+            if self
+                .self_writes
+                .iter()
+                .any(|w| self.block_at(w.target).map(|b| b.start) == Some(block.start))
+            {
+                attrs.push_str(", color=red");
+            }
+            writeln!(out, "    \"{}\" [{attrs}];", block.start).unwrap();
+
+            let mut edge = |t: &Target, attrs: &str| {
+                match t {
+                    Target::Static(t) if *t < 0 || self.invalid.contains(&(*t as usize)) => {
+                        bad.insert(*t);
+                    }
+                    Target::Static(_) => {}
+                    Target::Dynamic => dynamic = true,
+                }
+                writeln!(out, "    \"{}\" -> {}{attrs};", block.start, node(t)).unwrap();
+            };
+
+            match block.exit {
+                Exit::Next(next) => edge(&Target::Static(next as i64), ""),
+                Exit::Branch { target, next } => {
+                    edge(&target, " [label=\"taken\"]");
+                    edge(&Target::Static(next as i64), " [label=\"not taken\"]");
+                }
+                Exit::Jump(target) => edge(&target, ""),
+                Exit::Halt => {}
+            }
+        }
+
+        for w in &self.self_writes {
+            let from = self.block_at(w.writer).unwrap().start;
+            let to = self.block_at(w.target).unwrap().start;
+            writeln!(
+                out,
+                "    \"{from}\" -> \"{to}\" [style=dashed, color=red, label=\"writes {}\"];",
+                w.target
+            )
+            .unwrap();
+        }
+
+        if dynamic {
+            out.push_str("    \"?\" [label=\"dynamic\", shape=diamond];\n");
+        }
+        for t in bad {
+            writeln!(out, "    \"{t}\" [label=\"invalid {t}\", shape=octagon];").unwrap();
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    /// Analyses everything from address 0 up to the highest address that
+    /// has been loaded or written.
+    pub fn cfg(&self) -> Cfg {
+        Cfg::new(&self.get_mem_range(0, self.memory.end()))
+    }
+}
+
+/// How `line` ends its block, or `None` if control just runs on.
+fn exit(line: &Line) -> Option<Exit> {
+    let Item::Instruction { opcode, operands } = &line.item else {
+        return None;
+    };
+    let next = line.addr + line.words.len();
+
+    let jump_if = |taken_if_nonzero: bool| {
+        let target = match operands[1] {
+            Operand::Immediate(t) => Target::Static(t),
+            _ => Target::Dynamic,
+        };
+        match operands[0] {
+            Operand::Immediate(c) if (c != 0) == taken_if_nonzero => Some(Exit::Jump(target)),
+            Operand::Immediate(_) => None,
+            _ => Some(Exit::Branch { target, next }),
+        }
+    };
+
+    match opcode {
+        Opcode::JumpIfTrue => jump_if(true),
+        Opcode::JumpIfFalse => jump_if(false),
+        Opcode::Halt => Some(Exit::Halt),
+        _ => None,
+    }
+}
+
+/// The address `line` writes to, if it writes with a position operand.
+fn written(line: &Line) -> Option<i64> {
+    let Item::Instruction { opcode, operands } = &line.item else {
+        return None;
+    };
+
+    match opcode {
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals | Opcode::Input => {
+            match operands.last()? {
+                Operand::Position(addr) => Some(*addr),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(cfg: &Cfg) -> Vec<(usize, usize, Exit)> {
+        cfg.blocks()
+            .map(|block| (block.start, block.end, block.exit))
+            .collect()
+    }
+
+    #[test]
+    fn blocks() {
+        // Day 5: compares its input to 8.
+        let cfg = Intcode::from(
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+             1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+             999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        )
+        .cfg();
+
+        let jump = |t| Exit::Jump(Target::Static(t));
+        assert_eq!(
+            shape(&cfg),
+            vec![
+                (
+                    0,
+                    9,
+                    Exit::Branch {
+                        target: Target::Static(22),
+                        next: 9
+                    }
+                ),
+                (
+                    9,
+                    16,
+                    Exit::Branch {
+                        target: Target::Static(31),
+                        next: 16
+                    }
+                ),
+                (16, 19, jump(36)),
+                (22, 31, jump(46)),
+                (31, 36, jump(46)),
+                (36, 45, jump(46)),
+                (46, 47, Exit::Halt),
+            ]
+        );
+        assert_eq!(cfg.block(0).unwrap().lines.len(), 3);
+        assert_eq!(cfg.block_at(40).unwrap().start, 36);
+        assert!(cfg.is_code(35));
+        assert!(!cfg.is_code(19));
+        assert!(!cfg.is_code(45));
+        assert_eq!(cfg.invalid().count(), 0);
+        assert!(cfg.self_writes().is_empty());
+    }
+
+    #[test]
+    fn dynamic_and_invalid() {
+        let cfg = Cfg::new(&[3, 20, 1006, 20, 7, 4, 20, 1005, 20, 13, 105, 1, 20, 42]);
+
+        assert_eq!(
+            shape(&cfg),
+            vec![
+                (
+                    0,
+                    5,
+                    Exit::Branch {
+                        target: Target::Static(7),
+                        next: 5
+                    }
+                ),
+                (5, 7, Exit::Next(7)),
+                (
+                    7,
+                    10,
+                    Exit::Branch {
+                        target: Target::Static(13),
+                        next: 10
+                    }
+                ),
+                (10, 13, Exit::Jump(Target::Dynamic)),
+            ]
+        );
+        assert_eq!(cfg.invalid().collect::<Vec<_>>(), vec![13]);
+
+        // A jump that is never taken does not end its block.
+        let cfg = Cfg::new(&[1106, 1, 99, 99]);
+        assert_eq!(shape(&cfg), vec![(0, 4, Exit::Halt)]);
+    }
+
+    #[test]
+    fn overlapping() {
+        // Jumps to 4, inside the add at 3. Both decodes fall through to
+        // the halt at 10.
+        let cfg = Cfg::new(&[1005, 20, 4, 1101, 104, 5, 1101, 1106, 1, 30, 99]);
+        assert_eq!(
+            shape(&cfg),
+            vec![
+                (
+                    0,
+                    3,
+                    Exit::Branch {
+                        target: Target::Static(4),
+                        next: 3
+                    }
+                ),
+                (3, 10, Exit::Next(10)),
+                (4, 10, Exit::Next(10)),
+                (10, 11, Exit::Halt),
+            ]
+        );
+        for block in cfg.blocks() {
+            for next in block.exit.successors() {
+                assert!(cfg.block(next).is_some(), "{} -> {next}", block.start);
+            }
+        }
+        assert!(cfg.dot().contains("    \"10\" [label=\"10: hlt\\l\", peripheries=2];\n"));
+    }
+
+    #[test]
+    fn self_modifying() {
+        let cfg = Cfg::new(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert_eq!(
+            cfg.self_writes(),
+            [
+                SelfWrite {
+                    writer: 0,
+                    target: 3,
+                    instruction: 0
+                },
+                SelfWrite {
+                    writer: 4,
+                    target: 0,
+                    instruction: 0
+                },
+            ]
+        );
+
+        // Writes to data, or through the relative base, are not listed.
+        assert!(Cfg::new(&[3, 5, 203, 0, 99, 0]).self_writes().is_empty());
+    }
+
+    #[test]
+    fn dot() {
+        assert_eq!(
+            Cfg::new(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]).dot(),
+            "digraph intcode {
+    node [shape=box, fontname=\"monospace\"];
+    \"0\" [label=\"0: add [9], [10], [3]\\l4: mul [3], [11], [0]\\l8: hlt\\l\", peripheries=2, color=red];
+    \"0\" -> \"0\" [style=dashed, color=red, label=\"writes 3\"];
+    \"0\" -> \"0\" [style=dashed, color=red, label=\"writes 0\"];
+}
+"
+        );
+
+        let dot = Cfg::new(&[3, 20, 1006, 20, 7, 4, 20, 1005, 20, 13, 105, 1, 20, 42]).dot();
+        assert!(dot.contains("    \"0\" -> \"7\" [label=\"taken\"];\n"));
+        assert!(dot.contains("    \"0\" -> \"5\" [label=\"not taken\"];\n"));
+        assert!(dot.contains("    \"5\" -> \"7\";\n"));
+        assert!(dot.contains("    \"10\" -> \"?\";\n"));
+        assert!(dot.contains("    \"?\" [label=\"dynamic\", shape=diamond];\n"));
+        assert!(dot.contains("    \"13\" [label=\"invalid 13\", shape=octagon];\n"));
+    }
+}
@@ -0,0 +1,820 @@
+//! A step debugger for [`Intcode`] machines.
+//!
+//! [`Debugger`] can be driven directly from code, or through
+//! [`Debugger::console`] which reads one command per line:
+//!
+//! ```text
+//! s, step [N]          execute N instructions (default 1)
+//! c, continue          run until a breakpoint, watchpoint or catch triggers
+//! b, break ADDR        break before executing the instruction at ADDR
+//! d, delete ADDR       remove the breakpoint at ADDR
+//! w, watch ADDR [r|w|rw]
+//!                      break before an instruction reads and/or writes ADDR
+//! unwatch ADDR         remove the watchpoint on ADDR
+//! catch input|output   break before input / after output (again to toggle)
+//! r, regs              show ip, rb and the input/output queues
+//! x, mem START [LEN]   dump LEN (default 8, at most 1024) words from START
+//! l, list [N]          disassemble N (default 5) instructions from ip
+//! i, input N...        queue input values
+//! o, output            take all pending output
+//! record on|off        start or stop keeping history for reverse execution
+//! rs, rstep [N]        undo N instructions (default 1)
+//! rc, rcontinue        undo until a breakpoint, watchpoint or catch triggers
+//! who ADDR             show the last recorded write to ADDR
+//! q, quit              leave the console
+//! ```
+//!
+//! Reverse execution only covers what happened while recording. Changing
+//! the machine through [`Debugger::machine_mut`] in the meantime is not
+//! recorded and may leave undone state inconsistent.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+
+use super::disasm::{self, Line};
+use super::{
+    relative_addr, to_addr, Hook, Intcode, IntcodeError, Opcode, ParameterMode, RunResult,
+};
+
+/// Most words `mem` dumps, and most lines `list` shows, in one command.
+const MAX_LISTING: usize = 1024;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Watch {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Why the debugger handed control back.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// A single step completed without anything else to report.
+    Step,
+    /// About to execute the instruction at this address.
+    Breakpoint(usize),
+    /// About to execute an instruction touching a watched address.
+    Watchpoint {
+        addr: usize,
+        access: Access,
+    },
+    /// About to execute an input instruction.
+    Input,
+    /// An output instruction just produced this value.
+    Output(i64),
+    Halted,
+    BlockedOnInput,
+    /// Reverse execution reached the oldest recorded instruction.
+    HistoryStart,
+}
+
+/// A recorded write, see [`Debugger::last_writer`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Writer {
+    /// The machine's [`Intcode::cycles`] count when the writing
+    /// instruction started.
+    pub cycle: u64,
+    pub ip: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+pub struct Debugger {
+    vm: Intcode,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, Watch>,
+    catch_input: bool,
+    catch_output: bool,
+    /// One entry per executed instruction while recording.
+    history: Option<Vec<Undo>>,
+}
+
+/// What it takes to undo one instruction.
+#[derive(Default)]
+struct Undo {
+    ip: usize,
+    rb: isize,
+    cycles: u64,
+    /// Address, old and new value.
+    writes: Vec<(usize, i64, i64)>,
+    input: Option<i64>,
+    output: bool,
+}
+
+impl Hook for Undo {
+    fn write(&mut self, addr: usize, old: i64, new: i64) {
+        self.writes.push((addr, old, new));
+    }
+
+    fn input(&mut self, value: i64) {
+        self.input = Some(value);
+    }
+
+    fn output(&mut self, _value: i64) {
+        self.output = true;
+    }
+}
+
+impl Watch {
+    fn matches(&self, access: Access) -> bool {
+        matches!(
+            (self, access),
+            (Watch::ReadWrite, _) | (Watch::Read, Access::Read) | (Watch::Write, Access::Write)
+        )
+    }
+}
+
+impl From<Intcode> for Debugger {
+    fn from(vm: Intcode) -> Self {
+        Self {
+            vm,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            catch_input: false,
+            catch_output: false,
+            history: None,
+        }
+    }
+}
+
+impl Debugger {
+    pub fn machine(&self) -> &Intcode {
+        &self.vm
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Intcode {
+        &mut self.vm
+    }
+
+    pub fn into_inner(self) -> Intcode {
+        self.vm
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn watch(&mut self, addr: usize, watch: Watch) {
+        self.watchpoints.insert(addr, watch);
+    }
+
+    pub fn unwatch(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr).is_some()
+    }
+
+    pub fn catch_input(&mut self, enabled: bool) {
+        self.catch_input = enabled;
+    }
+
+    pub fn catch_output(&mut self, enabled: bool) {
+        self.catch_output = enabled;
+    }
+
+    /// Starts or stops recording history. Stopping discards what was
+    /// recorded so far.
+    pub fn record(&mut self, enabled: bool) {
+        if enabled {
+            self.history.get_or_insert_with(Vec::new);
+        } else {
+            self.history = None;
+        }
+    }
+
+    pub fn recording(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Number of instructions that can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Executes exactly one instruction, ignoring breakpoints and
+    /// watchpoints.
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        let is_output = matches!(self.vm.next_instr(), Ok(ins) if ins.opcode == Opcode::Output);
+
+        let result = match &mut self.history {
+            Some(history) => {
+                let mut undo = Undo {
+                    ip: self.vm.ip,
+                    rb: self.vm.rb,
+                    cycles: self.vm.cycles,
+                    ..Default::default()
+                };
+                let result = self.vm.try_step_hooked(&mut undo)?;
+                if result == RunResult::Ok {
+                    history.push(undo);
+                }
+                result
+            }
+            None => self.vm.try_step()?,
+        };
+
+        Ok(match result {
+            RunResult::Ok if is_output && self.catch_output => {
+                Stop::Output(*self.vm.output.back().unwrap())
+            }
+            RunResult::Ok => Stop::Step,
+            RunResult::Halted => Stop::Halted,
+            RunResult::BlockedOnInput => Stop::BlockedOnInput,
+            RunResult::BudgetExhausted => unreachable!("single steps have no budget"),
+        })
+    }
+
+    /// Runs until something stops the machine. The instruction at the
+    /// current `ip` always executes, so continuing from a breakpoint does
+    /// not immediately stop on it again.
+    pub fn cont(&mut self) -> Result<Stop, IntcodeError> {
+        let mut first = true;
+
+        loop {
+            if !first {
+                if let Some(stop) = self.check() {
+                    return Ok(stop);
+                }
+            }
+            first = false;
+
+            match self.step()? {
+                Stop::Step => continue,
+                other => return Ok(other),
+            }
+        }
+    }
+
+    /// Undoes the most recently recorded instruction. Returns `false` if
+    /// there is nothing to undo.
+    ///
+    /// Consumed input goes back to the front of the input queue. Output is
+    /// taken back off the output queue unless it has been read since.
+    pub fn step_back(&mut self) -> bool {
+        let Some(undo) = self.history.as_mut().and_then(Vec::pop) else {
+            return false;
+        };
+
+        for (addr, old, _) in undo.writes.into_iter().rev() {
+            self.vm.mem_set(addr, old);
+        }
+        self.vm.ip = undo.ip;
+        self.vm.rb = undo.rb;
+        self.vm.cycles = undo.cycles;
+        if let Some(value) = undo.input {
+            self.vm.input.push_front(value);
+        }
+        // Later outputs have already been undone, so this one is the newest
+        // and sits at the back of the queue. Output is taken from the
+        // front, so if it has been read, so has everything before it and
+        // the queue is empty.
+        if undo.output {
+            self.vm.output.pop_back();
+        }
+
+        true
+    }
+
+    /// Undoes instructions until one would stop [`Debugger::cont`] when
+    /// run forward again, or history runs out. The most recent instruction
+    /// is always undone, so reversing from a breakpoint does not
+    /// immediately stop on it again.
+    pub fn reverse_cont(&mut self) -> Stop {
+        while self.step_back() {
+            if let Some(stop) = self.check() {
+                return stop;
+            }
+        }
+
+        Stop::HistoryStart
+    }
+
+    /// The most recent recorded write to `addr`.
+    pub fn last_writer(&self, addr: usize) -> Option<Writer> {
+        self.history.iter().flatten().rev().find_map(|undo| {
+            let (_, old, new) = undo.writes.iter().rev().find(|(a, _, _)| *a == addr)?;
+            Some(Writer {
+                cycle: undo.cycles,
+                ip: undo.ip,
+                old: *old,
+                new: *new,
+            })
+        })
+    }
+
+    /// Disassembles the instruction at `addr`.
+    pub fn line_at(&self, addr: usize) -> Line {
+        let words: Vec<i64> = (0..4)
+            .map_while(|i| addr.checked_add(i))
+            .map(|a| self.vm.get_mem(a))
+            .collect();
+        let mut line = disasm::disassemble(&words).remove(0);
+        line.addr = addr;
+        line
+    }
+
+    fn check(&self) -> Option<Stop> {
+        if self.breakpoints.contains(&self.vm.ip) {
+            return Some(Stop::Breakpoint(self.vm.ip));
+        }
+
+        if self.catch_input
+            && matches!(self.vm.next_instr(), Ok(ins) if ins.opcode == Opcode::Input)
+        {
+            return Some(Stop::Input);
+        }
+
+        self.accesses()
+            .into_iter()
+            .find_map(|(addr, access)| match self.watchpoints.get(&addr) {
+                Some(watch) if watch.matches(access) => Some(Stop::Watchpoint { addr, access }),
+                _ => None,
+            })
+    }
+
+    /// Memory the next instruction will touch, worked out without
+    /// executing it.
+    fn accesses(&self) -> Vec<(usize, Access)> {
+        let vm = &self.vm;
+        let Ok(ins) = vm.next_instr() else {
+            return Vec::new();
+        };
+
+        let addr = |param: usize| {
+            let n = vm.mem_get_addr(vm.ip + param + 1);
+            match ins.p_mode[param] {
+                ParameterMode::Position => to_addr(n).ok(),
+                ParameterMode::Immediate => None,
+                ParameterMode::Relative => relative_addr(&vm.rb, n).ok(),
+            }
+        };
+        let read = |param| addr(param).map(|a| (a, Access::Read));
+        let write = |param| addr(param).map(|a| (a, Access::Write));
+
+        let accesses = match ins.opcode {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+                vec![read(0), read(1), write(2)]
+            }
+            Opcode::Input if vm.input.is_empty() => vec![],
+            Opcode::Input => vec![write(0)],
+            Opcode::Output | Opcode::RelativeBase => vec![read(0)],
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let taken = match vm.mem_get(ins.p_mode[0], 1) {
+                    Ok(cond) => (cond != 0) == (ins.opcode == Opcode::JumpIfTrue),
+                    Err(_) => false,
+                };
+                vec![read(0), if taken { read(1) } else { None }]
+            }
+            Opcode::Halt => vec![],
+        };
+
+        accesses.into_iter().flatten().collect()
+    }
+
+    /// Runs an interactive session, reading commands from `input` and
+    /// writing results to `out` until `quit` or end of input.
+    pub fn console<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}", self.line_at(self.vm.ip))?;
+
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let Some(cmd) = words.next() else {
+                continue;
+            };
+            let args: Vec<&str> = words.collect();
+            let num = |i: usize| args.get(i).and_then(|a| a.parse::<usize>().ok());
+
+            match cmd {
+                "s" | "step" => {
+                    for _ in 0..num(0).unwrap_or(1) {
+                        let stop = self.step();
+                        let done = !matches!(stop, Ok(Stop::Step | Stop::Output(_)));
+                        self.report(&mut out, stop)?;
+                        if done {
+                            break;
+                        }
+                    }
+                }
+                "c" | "continue" => {
+                    let stop = self.cont();
+                    self.report(&mut out, stop)?;
+                }
+                "b" | "break" => match num(0) {
+                    Some(addr) => {
+                        self.add_breakpoint(addr);
+                        writeln!(out, "breakpoint at {addr}")?;
+                    }
+                    None => writeln!(out, "usage: break ADDR")?,
+                },
+                "d" | "delete" => match num(0) {
+                    Some(addr) if self.remove_breakpoint(addr) => {
+                        writeln!(out, "deleted breakpoint at {addr}")?
+                    }
+                    Some(addr) => writeln!(out, "no breakpoint at {addr}")?,
+                    None => writeln!(out, "usage: delete ADDR")?,
+                },
+                "w" | "watch" => {
+                    let watch = match args.get(1).copied() {
+                        None | Some("rw") => Some(Watch::ReadWrite),
+                        Some("r") => Some(Watch::Read),
+                        Some("w") => Some(Watch::Write),
+                        Some(_) => None,
+                    };
+                    match (num(0), watch) {
+                        (Some(addr), Some(watch)) => {
+                            self.watch(addr, watch);
+                            writeln!(out, "watching {addr}")?;
+                        }
+                        _ => writeln!(out, "usage: watch ADDR [r|w|rw]")?,
+                    }
+                }
+                "unwatch" => match num(0) {
+                    Some(addr) if self.unwatch(addr) => writeln!(out, "unwatched {addr}")?,
+                    Some(addr) => writeln!(out, "no watchpoint on {addr}")?,
+                    None => writeln!(out, "usage: unwatch ADDR")?,
+                },
+                "catch" => match args.first().copied() {
+                    Some("input") => {
+                        self.catch_input = !self.catch_input;
+                        writeln!(out, "catch input: {}", self.catch_input)?;
+                    }
+                    Some("output") => {
+                        self.catch_output = !self.catch_output;
+                        writeln!(out, "catch output: {}", self.catch_output)?;
+                    }
+                    _ => writeln!(out, "usage: catch input|output")?,
+                },
+                "r" | "regs" => {
+                    let join = |it: &mut dyn Iterator<Item = i64>| {
+                        it.map(|n| n.to_string()).collect::<Vec<_>>().join(",")
+                    };
+                    writeln!(out, "ip={} rb={}", self.vm.ip, self.vm.rb)?;
+                    writeln!(out, "input=[{}]", join(&mut self.vm.pending_input()))?;
+                    writeln!(out, "output=[{}]", join(&mut self.vm.pending_output()))?;
+                }
+                "x" | "mem" => match (num(0), num(1).unwrap_or(8)) {
+                    (Some(_), len) if len > MAX_LISTING => {
+                        writeln!(out, "at most {MAX_LISTING} words at a time")?
+                    }
+                    (Some(start), len) if start.checked_add(len).is_none() => {
+                        writeln!(out, "address out of range")?
+                    }
+                    (Some(start), len) => {
+                        for (chunk, words) in self
+                            .vm
+                            .get_mem_range(start, start + len)
+                            .chunks(8)
+                            .enumerate()
+                        {
+                            let words = words
+                                .iter()
+                                .map(|w| w.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            writeln!(out, "{:>5}: {words}", start + chunk * 8)?;
+                        }
+                    }
+                    (None, _) => writeln!(out, "usage: mem START [LEN]")?,
+                },
+                "l" | "list" => {
+                    let mut addr = Some(self.vm.ip);
+                    for _ in 0..num(0).unwrap_or(5).min(MAX_LISTING) {
+                        let Some(at) = addr else {
+                            break;
+                        };
+                        let line = self.line_at(at);
+                        addr = at.checked_add(line.words.len());
+                        writeln!(out, "{line}")?;
+                    }
+                }
+                "i" | "input" => {
+                    let values: Result<Vec<i64>, _> = args.iter().map(|a| a.parse()).collect();
+                    match values {
+                        Ok(values) if !values.is_empty() => {
+                            values.into_iter().for_each(|v| self.vm.input(v))
+                        }
+                        _ => writeln!(out, "usage: input N...")?,
+                    }
+                }
+                "o" | "output" => {
+                    while let Some(v) = self.vm.output() {
+                        writeln!(out, "{v}")?;
+                    }
+                }
+                "record" => match args.first().copied() {
+                    Some(arg @ ("on" | "off")) => {
+                        self.record(arg == "on");
+                        writeln!(out, "recording: {arg}")?;
+                    }
+                    _ => writeln!(out, "usage: record on|off")?,
+                },
+                "rs" | "rstep" => {
+                    let n = num(0).unwrap_or(1);
+                    let undone = (0..n).take_while(|_| self.step_back()).count();
+                    if undone < n {
+                        writeln!(out, "start of history")?;
+                    }
+                    writeln!(out, "{}", self.line_at(self.vm.ip))?;
+                }
+                "rc" | "rcontinue" => {
+                    let stop = self.reverse_cont();
+                    self.report(&mut out, Ok(stop))?;
+                }
+                "who" => match num(0) {
+                    Some(addr) => match self.last_writer(addr) {
+                        Some(w) => {
+                            writeln!(out, "{addr}: {} -> {} at cycle {}", w.old, w.new, w.cycle)?;
+                            writeln!(out, "{}", self.line_at(w.ip))?;
+                        }
+                        None => writeln!(out, "no recorded write to {addr}")?,
+                    },
+                    None => writeln!(out, "usage: who ADDR")?,
+                },
+                "q" | "quit" => break,
+                other => writeln!(out, "unknown command '{other}'")?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn report<W: Write>(&self, out: &mut W, stop: Result<Stop, IntcodeError>) -> io::Result<()> {
+        match stop {
+            Ok(Stop::Step) => {}
+            Ok(Stop::Breakpoint(addr)) => writeln!(out, "breakpoint at {addr}")?,
+            Ok(Stop::Watchpoint { addr, access }) => {
+                let access = match access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                };
+                writeln!(out, "watchpoint: {access} of {addr}")?
+            }
+            Ok(Stop::Input) => writeln!(out, "stopped before input")?,
+            Ok(Stop::Output(v)) => writeln!(out, "output {v}")?,
+            Ok(Stop::Halted) => return writeln!(out, "halted"),
+            Ok(Stop::BlockedOnInput) => writeln!(out, "blocked on input")?,
+            Ok(Stop::HistoryStart) => writeln!(out, "start of history")?,
+            Err(e) => return writeln!(out, "error: {e}"),
+        }
+
+        writeln!(out, "{}", self.line_at(self.vm.ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // in [rb+9]; eq [rb+9], #8, [rb+10]; out [rb+10]; hlt
+    const EQUALS_8: &str = "203,9,21208,9,8,10,204,10,99";
+
+    #[test]
+    fn step_and_breakpoints() {
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        dbg.machine_mut().input(8);
+        dbg.add_breakpoint(6);
+
+        assert_eq!(dbg.step(), Ok(Stop::Step));
+        assert_eq!(dbg.machine().ip(), 2);
+        assert_eq!(dbg.cont(), Ok(Stop::Breakpoint(6)));
+        // Continuing from a breakpoint runs past it.
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+        assert_eq!(dbg.machine_mut().output(), Some(1));
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        dbg.machine_mut().input(3);
+        dbg.watch(10, Watch::Read);
+        dbg.watch(9, Watch::Write);
+
+        // The first instruction always runs, so the write to 9 is missed.
+        assert_eq!(
+            dbg.cont(),
+            Ok(Stop::Watchpoint {
+                addr: 10,
+                access: Access::Read
+            })
+        );
+        assert_eq!(dbg.machine().ip(), 6);
+
+        assert!(dbg.unwatch(9));
+        assert!(!dbg.unwatch(9));
+
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        dbg.machine_mut().input(3);
+        dbg.watch(10, Watch::Write);
+        assert_eq!(
+            dbg.cont(),
+            Ok(Stop::Watchpoint {
+                addr: 10,
+                access: Access::Write
+            })
+        );
+        assert_eq!(dbg.machine().ip(), 2);
+    }
+
+    #[test]
+    fn untaken_jump_does_not_read_target() {
+        // jnz #0, [5]; hlt; .data 0
+        let mut dbg = Debugger::from(Intcode::from("105,0,5,99,99,0"));
+        dbg.watch(5, Watch::Read);
+        dbg.add_breakpoint(0);
+        assert_eq!(dbg.step(), Ok(Stop::Step));
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+    }
+
+    #[test]
+    fn catch_io() {
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        dbg.catch_output(true);
+        assert_eq!(dbg.cont(), Ok(Stop::BlockedOnInput));
+
+        dbg.catch_input(true);
+        dbg.machine_mut().input(8);
+        dbg.machine_mut().input(7);
+        assert_eq!(dbg.step(), Ok(Stop::Step));
+        assert_eq!(dbg.cont(), Ok(Stop::Output(1)));
+        assert_eq!(dbg.machine().pending_input().collect::<Vec<_>>(), vec![7]);
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+    }
+
+    #[test]
+    fn faults_are_reported() {
+        let mut dbg = Debugger::from(Intcode::from("1101,1,1,0,42"));
+        assert_eq!(dbg.cont().unwrap_err().ip, 4);
+    }
+
+    #[test]
+    fn step_back() {
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        dbg.record(true);
+        dbg.machine_mut().input(8);
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+        assert_eq!(dbg.history_len(), 3);
+
+        assert_eq!(
+            dbg.last_writer(10),
+            Some(Writer {
+                cycle: 1,
+                ip: 2,
+                old: 0,
+                new: 1
+            })
+        );
+        assert_eq!(dbg.last_writer(9).map(|w| (w.ip, w.new)), Some((0, 8)));
+        assert_eq!(dbg.last_writer(0), None);
+
+        // Undoing the output takes it back.
+        assert!(dbg.step_back());
+        assert_eq!(dbg.machine().ip(), 6);
+        assert_eq!(dbg.machine().cycles(), 2);
+        assert_eq!(dbg.machine().pending_output().count(), 0);
+
+        assert!(dbg.step_back());
+        assert_eq!(dbg.machine().get_mem_range(9, 11), vec![8, 0]);
+
+        // Undoing the input puts it back in the queue.
+        assert!(dbg.step_back());
+        assert_eq!(dbg.machine().ip(), 0);
+        assert_eq!(dbg.machine().get_mem_range(9, 11), vec![0, 0]);
+        assert_eq!(dbg.machine().pending_input().collect::<Vec<_>>(), vec![8]);
+        assert!(!dbg.step_back());
+
+        // Output that has been read stays read.
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+        assert_eq!(dbg.machine_mut().output(), Some(1));
+        assert!(dbg.step_back());
+        assert_eq!(dbg.machine().ip(), 6);
+        assert_eq!(dbg.machine_mut().output(), None);
+    }
+
+    #[test]
+    fn step_back_after_partial_read() {
+        let mut dbg = Debugger::from(Intcode::from("104,1,104,2,99"));
+        dbg.record(true);
+        dbg.step().unwrap();
+        dbg.step().unwrap();
+        assert_eq!(dbg.machine_mut().output(), Some(1));
+
+        assert!(dbg.step_back());
+        assert_eq!(dbg.machine().pending_output().count(), 0);
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+        assert_eq!(dbg.machine().pending_output().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn reverse_cont() {
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        dbg.record(true);
+        dbg.add_breakpoint(2);
+        dbg.watch(9, Watch::Write);
+        dbg.machine_mut().input(8);
+
+        assert_eq!(dbg.cont(), Ok(Stop::Breakpoint(2)));
+        assert_eq!(dbg.cont(), Ok(Stop::Halted));
+
+        assert_eq!(dbg.reverse_cont(), Stop::Breakpoint(2));
+        assert_eq!(
+            dbg.reverse_cont(),
+            Stop::Watchpoint {
+                addr: 9,
+                access: Access::Write
+            }
+        );
+        assert_eq!(dbg.reverse_cont(), Stop::HistoryStart);
+        assert_eq!(dbg.machine().ip(), 0);
+
+        // Nothing is recorded unless asked for.
+        dbg.record(false);
+        dbg.cont().unwrap();
+        assert!(!dbg.step_back());
+        assert_eq!(dbg.reverse_cont(), Stop::HistoryStart);
+    }
+
+    #[test]
+    fn console() {
+        let script = "b 6\nc\nr\nx 8 3\nl 2\nw 10 r\nc\ni 8\nc\nc\no\nbogus\nq\ns\n";
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        let mut out = Vec::new();
+        dbg.console(script.as_bytes(), &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "    0: 203,9                    in [rb+9]\n\
+             breakpoint at 6\n\
+             blocked on input\n\
+             \x20   0: 203,9                    in [rb+9]\n\
+             ip=0 rb=0\n\
+             input=[]\n\
+             output=[]\n\
+             \x20   8: 99 0 0\n\
+             \x20   0: 203,9                    in [rb+9]\n\
+             \x20   2: 21208,9,8,10             eq [rb+9], #8, [rb+10]\n\
+             watching 10\n\
+             blocked on input\n\
+             \x20   0: 203,9                    in [rb+9]\n\
+             breakpoint at 6\n\
+             \x20   6: 204,10                   out [rb+10]\n\
+             halted\n\
+             1\n\
+             unknown command 'bogus'\n"
+        );
+    }
+
+    #[test]
+    fn console_bounds() {
+        let script =
+            "x 18446744073709551615 8\nx 0 100000\nx 18446744073709551614 1\nl 99999999999\n";
+        let mut dbg = Debugger::from(Intcode::from("99"));
+        let mut out = Vec::new();
+        dbg.console(script.as_bytes(), &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines[1], "address out of range");
+        assert_eq!(lines[2], "at most 1024 words at a time");
+        assert_eq!(lines[3], "18446744073709551614: 0");
+        assert_eq!(lines.len(), 4 + MAX_LISTING);
+        assert_eq!(
+            dbg.line_at(usize::MAX).to_string(),
+            "18446744073709551615: 0                        .data 0"
+        );
+    }
+
+    #[test]
+    fn console_reverse() {
+        let script = "record on\nc\ni 8\nc\nwho 10\nrs 2\nrc\nwho 3\nrecord off\nrs\n";
+        let mut dbg = Debugger::from(Intcode::from(EQUALS_8));
+        let mut out = Vec::new();
+        dbg.console(script.as_bytes(), &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "    0: 203,9                    in [rb+9]\n\
+             recording: on\n\
+             blocked on input\n\
+             \x20   0: 203,9                    in [rb+9]\n\
+             halted\n\
+             10: 0 -> 1 at cycle 1\n\
+             \x20   2: 21208,9,8,10             eq [rb+9], #8, [rb+10]\n\
+             \x20   2: 21208,9,8,10             eq [rb+9], #8, [rb+10]\n\
+             start of history\n\
+             \x20   0: 203,9                    in [rb+9]\n\
+             no recorded write to 3\n\
+             recording: off\n\
+             start of history\n\
+             \x20   0: 203,9                    in [rb+9]\n"
+        );
+    }
+}
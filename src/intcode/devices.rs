@@ -0,0 +1,316 @@
+//! Input and output devices for [`Intcode::run_with`].
+//!
+//! A machine's own queues ([`Intcode::input`] / [`Intcode::output`]) are
+//! just `VecDeque<i64>` devices. Anything else implementing
+//! [`InputSource`] or [`OutputSink`] can be wired in instead: closures
+//! ([`input_fn`], [`output_fn`]), iterators ([`input_iter`]), channels,
+//! text streams ([`ReadSource`], [`WriteSink`]), or another machine.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc;
+
+use super::{Intcode, Memory};
+
+pub trait InputSource {
+    /// Returns the next input value, or `None` if none is available, in
+    /// which case the machine reports [`super::RunResult::BlockedOnInput`].
+    fn read(&mut self) -> Option<i64>;
+}
+
+pub trait OutputSink {
+    fn write(&mut self, value: i64);
+}
+
+impl<T: InputSource + ?Sized> InputSource for &mut T {
+    fn read(&mut self) -> Option<i64> {
+        (**self).read()
+    }
+}
+
+impl<T: OutputSink + ?Sized> OutputSink for &mut T {
+    fn write(&mut self, value: i64) {
+        (**self).write(value)
+    }
+}
+
+impl InputSource for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+impl OutputSink for VecDeque<i64> {
+    fn write(&mut self, value: i64) {
+        self.push_back(value)
+    }
+}
+
+impl OutputSink for Vec<i64> {
+    fn write(&mut self, value: i64) {
+        self.push(value)
+    }
+}
+
+/// Blocks until a value arrives; a disconnected channel has no more input.
+impl InputSource for mpsc::Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+/// Values sent after the receiver hangs up are dropped.
+impl OutputSink for mpsc::Sender<i64> {
+    fn write(&mut self, value: i64) {
+        self.send(value).ok();
+    }
+}
+
+/// Reading from a machine takes its pending output.
+impl<M: Memory> InputSource for Intcode<M> {
+    fn read(&mut self) -> Option<i64> {
+        self.output()
+    }
+}
+
+/// Writing to a machine queues input for it.
+impl<M: Memory> OutputSink for Intcode<M> {
+    fn write(&mut self, value: i64) {
+        self.input(value)
+    }
+}
+
+pub struct InputFn<F>(F);
+
+pub struct OutputFn<F>(F);
+
+pub struct InputIter<I>(I);
+
+pub fn input_fn<F: FnMut() -> Option<i64>>(f: F) -> InputFn<F> {
+    InputFn(f)
+}
+
+pub fn output_fn<F: FnMut(i64)>(f: F) -> OutputFn<F> {
+    OutputFn(f)
+}
+
+pub fn input_iter<I: IntoIterator<Item = i64>>(iter: I) -> InputIter<I::IntoIter> {
+    InputIter(iter.into_iter())
+}
+
+impl<F: FnMut() -> Option<i64>> InputSource for InputFn<F> {
+    fn read(&mut self) -> Option<i64> {
+        (self.0)()
+    }
+}
+
+impl<F: FnMut(i64)> OutputSink for OutputFn<F> {
+    fn write(&mut self, value: i64) {
+        (self.0)(value)
+    }
+}
+
+impl<I: Iterator<Item = i64>> InputSource for InputIter<I> {
+    fn read(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+/// Reads numbers separated by whitespace or commas from a text stream.
+/// End of stream counts as running out of input. So does a read error or
+/// a line holding a word that is not a number, except that input then
+/// stays stopped and the error is kept for [`ReadSource::error`]; no value
+/// from such a line is queued.
+pub struct ReadSource<R> {
+    reader: R,
+    pending: VecDeque<i64>,
+    line: usize,
+    error: Option<io::Error>,
+}
+
+/// Writes each value on its own line. Write errors are ignored.
+pub struct WriteSink<W>(W);
+
+impl<R: BufRead> ReadSource<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: VecDeque::new(),
+            line: 0,
+            error: None,
+        }
+    }
+
+    /// Why input stopped, if it was not the end of the stream.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl ReadSource<io::StdinLock<'static>> {
+    pub fn stdin() -> Self {
+        Self::new(io::stdin().lock())
+    }
+}
+
+impl<R: BufRead> InputSource for ReadSource<R> {
+    fn read(&mut self) -> Option<i64> {
+        while self.pending.is_empty() && self.error.is_none() {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+
+            let words: Result<Vec<i64>, _> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty())
+                .map(|word| word.parse().map_err(|_| word))
+                .collect();
+            match words {
+                Ok(words) => self.pending.extend(words),
+                Err(word) => {
+                    let message = format!("line {}: invalid number '{word}'", self.line);
+                    self.error = Some(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+impl<W: Write> WriteSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self(writer)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl WriteSink<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> OutputSink for WriteSink<W> {
+    fn write(&mut self, value: i64) {
+        writeln!(self.0, "{value}").ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::RunResult;
+    use std::thread;
+
+    // Outputs double each input until it reads a zero.
+    const DOUBLER: &str = "3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0";
+
+    #[test]
+    fn closures_and_iterators() {
+        let mut intcode = Intcode::from(DOUBLER);
+        let mut seen = Vec::new();
+        let result = intcode.run_with(&mut input_iter([1, 2, 3]), &mut output_fn(|v| seen.push(v)));
+        assert_eq!(result, Ok(RunResult::BlockedOnInput));
+        assert_eq!(seen, vec![2, 4, 6]);
+
+        let mut count = 0;
+        let mut out = Vec::new();
+        let result = intcode.run_with(
+            &mut input_fn(|| {
+                count += 1;
+                Some(5 - count)
+            }),
+            &mut out,
+        );
+        assert_eq!(result, Ok(RunResult::Halted));
+        assert_eq!(out, vec![8, 6, 4, 2]);
+    }
+
+    #[test]
+    fn own_queues_untouched() {
+        let mut intcode = Intcode::from(DOUBLER);
+        intcode.input(7);
+        let mut out = VecDeque::new();
+        intcode
+            .run_with(&mut VecDeque::from([1, 0]), &mut out)
+            .unwrap();
+        assert_eq!(out, [2]);
+        assert_eq!(intcode.pending_input().collect::<Vec<_>>(), vec![7]);
+        assert_eq!(intcode.output(), None);
+    }
+
+    #[test]
+    fn machine_to_machine() {
+        let mut first = Intcode::from(DOUBLER);
+        first.input(1);
+        first.input(5);
+        first.input(0);
+        first.run();
+
+        // Reading from a machine drains its output queue...
+        let mut second = Intcode::from(DOUBLER);
+        let mut out = Vec::new();
+        assert_eq!(
+            second.run_with(&mut first, &mut out),
+            Ok(RunResult::BlockedOnInput)
+        );
+        assert_eq!(out, vec![4, 20]);
+
+        // ...and writing to one fills its input queue.
+        let mut third = Intcode::from(DOUBLER);
+        assert_eq!(
+            second.run_with(&mut input_iter([3, 0]), &mut third),
+            Ok(RunResult::Halted)
+        );
+        third.input(0);
+        third.run();
+        assert_eq!(third.output(), Some(12));
+    }
+
+    #[test]
+    fn channels() {
+        let (in_tx, mut in_rx) = mpsc::channel();
+        let (mut out_tx, out_rx) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            let mut intcode = Intcode::from(DOUBLER);
+            intcode.run_with(&mut in_rx, &mut out_tx)
+        });
+
+        in_tx.send(21).unwrap();
+        assert_eq!(out_rx.recv(), Ok(42));
+        in_tx.send(0).unwrap();
+        assert_eq!(handle.join().unwrap(), Ok(RunResult::Halted));
+        assert!(out_rx.recv().is_err());
+    }
+
+    #[test]
+    fn text_streams() {
+        let mut intcode = Intcode::from(DOUBLER);
+        let mut input = ReadSource::new("1, 2\n\n3 x\n".as_bytes());
+        let mut output = WriteSink::new(Vec::new());
+
+        assert_eq!(
+            intcode.run_with(&mut input, &mut output),
+            Ok(RunResult::BlockedOnInput)
+        );
+        assert_eq!(String::from_utf8(output.into_inner()).unwrap(), "2\n4\n");
+
+        // Nothing from the bad line is queued, and input stays stopped.
+        assert_eq!(
+            input.error().unwrap().to_string(),
+            "line 3: invalid number 'x'"
+        );
+        assert_eq!(input.read(), None);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+use super::{Instruction, Opcode, ParameterMode};
+
+/// One decoded entry of a listing: either a full instruction or a single
+/// word that could not be decoded as one.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Line {
+    pub addr: usize,
+    pub words: Vec<i64>,
+    pub item: Item,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Item {
+    Instruction {
+        opcode: Opcode,
+        operands: Vec<Operand>,
+    },
+    Data(i64),
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Operand {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl Operand {
+    fn new(mode: ParameterMode, n: i64) -> Self {
+        match mode {
+            ParameterMode::Position => Operand::Position(n),
+            ParameterMode::Immediate => Operand::Immediate(n),
+            ParameterMode::Relative => Operand::Relative(n),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Position(n) => write!(f, "[{n}]"),
+            Operand::Immediate(n) => write!(f, "#{n}"),
+            Operand::Relative(n) if *n < 0 => write!(f, "[rb-{}]", n.unsigned_abs()),
+            Operand::Relative(n) => write!(f, "[rb+{n}]"),
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Instruction { opcode, operands } => {
+                write!(f, "{}", opcode.mnemonic())?;
+
+                for (i, op) in operands.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{sep}{op}")?;
+                }
+
+                Ok(())
+            }
+            Item::Data(n) => write!(f, ".data {n}"),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = self
+            .words
+            .iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        write!(f, "{:>5}: {:<24} {}", self.addr, words, self.item)
+    }
+}
+
+/// Decodes the instruction at `addr` the same way the VM does. A word
+/// that does not decode, or whose operands would run past the end of the
+/// program, comes back as `.data`. Returns `None` if `addr` is outside
+/// the program.
+pub fn decode(program: &[i64], addr: usize) -> Option<Line> {
+    let word = *program.get(addr)?;
+
+    Some(match Instruction::try_from(word) {
+        Ok(ins) if addr + ins.len <= program.len() => {
+            let words = program[addr..addr + ins.len].to_vec();
+            let operands = words[1..]
+                .iter()
+                .zip(ins.p_mode)
+                .map(|(n, mode)| Operand::new(mode, *n))
+                .collect();
+
+            Line {
+                addr,
+                words,
+                item: Item::Instruction {
+                    opcode: ins.opcode,
+                    operands,
+                },
+            }
+        }
+        _ => Line {
+            addr,
+            words: vec![word],
+            item: Item::Data(word),
+        },
+    })
+}
+
+/// Walks `program` from address 0, decoding one instruction or data word
+/// after another.
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+
+    while let Some(line) = decode(program, addr) {
+        addr += line.words.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Renders a full listing, one line per instruction or data word.
+pub fn listing(program: &[i64]) -> String {
+    disassemble(program)
+        .iter()
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Intcode;
+
+    #[test]
+    fn operands() {
+        let lines = disassemble(&[21101, 3, 5, 100, 2201, -4, 7, 0, 99]);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].item.to_string(), "add #3, #5, [rb+100]");
+        assert_eq!(lines[1].item.to_string(), "add [rb-4], [rb+7], [0]");
+        assert_eq!(lines[2].item.to_string(), "hlt");
+        assert_eq!(lines[2].addr, 8);
+    }
+
+    #[test]
+    fn data_fallback() {
+        // 42 is not an opcode, 1101 at the end is missing its operands.
+        let lines = disassemble(&[104, 7, 42, 1101, 1]);
+        assert_eq!(
+            lines,
+            vec![
+                Line {
+                    addr: 0,
+                    words: vec![104, 7],
+                    item: Item::Instruction {
+                        opcode: Opcode::Output,
+                        operands: vec![Operand::Immediate(7)],
+                    },
+                },
+                Line {
+                    addr: 2,
+                    words: vec![42],
+                    item: Item::Data(42),
+                },
+                Line {
+                    addr: 3,
+                    words: vec![1101],
+                    item: Item::Data(1101),
+                },
+                Line {
+                    addr: 4,
+                    words: vec![1],
+                    item: Item::Data(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_out_of_range() {
+        assert_eq!(decode(&[99], 5), None);
+        assert_eq!(decode(&[99], 1), None);
+        assert_eq!(
+            decode(&[99], 0).map(|line| line.item.to_string()),
+            Some("hlt".into())
+        );
+    }
+
+    #[test]
+    fn listing() {
+        let intcode = Intcode::from("3,9,8,9,10,9,4,9,99,-1,8");
+        let text = intcode
+            .disassemble()
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            text,
+            vec![
+                "    0: 3,9                      in [9]",
+                "    2: 8,9,10,9                 eq [9], [10], [9]",
+                "    6: 4,9                      out [9]",
+                "    8: 99                       hlt",
+                "    9: -1                       .data -1",
+                "   10: 8                        .data 8",
+            ]
+        );
+        assert_eq!(
+            super::listing(&[99, 5]),
+            "    0: 99                       hlt\n    1: 5                        .data 5\n"
+        );
+    }
+}
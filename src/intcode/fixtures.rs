@@ -0,0 +1,30 @@
+//! Programs shared by the tests of several modules.
+
+use super::{asm, Intcode};
+
+/// Day 9 example: outputs a copy of itself.
+pub(super) const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+
+/// Prints "? " and echoes each line typed at it until it sees a `q`,
+/// then prints a large number and halts.
+pub(super) fn echo() -> Intcode {
+    let program = asm::assemble(
+        "prompt: out #63
+                 out #32
+         read:   in [c]
+                 eq [c], #10, [t]
+                 jnz [t], #eol
+                 eq [c], #113, [t]
+                 jnz [t], #quit
+                 out [c]
+                 jnz #1, #read
+         eol:    out #10
+                 jnz #1, #prompt
+         quit:   out #1000000
+                 hlt
+         c:      .data 0
+         t:      .data 0",
+    )
+    .unwrap();
+    Intcode::from(&program[..])
+}
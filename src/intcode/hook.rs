@@ -0,0 +1,79 @@
+//! Observing a machine as it executes, see [`Intcode::try_run_hooked`].
+//!
+//! The interpreter is generic over the hook, so the plain run methods,
+//! which use the no-op `()` hook, compile to the same code as if hooks did
+//! not exist.
+
+#[cfg(doc)]
+use super::Intcode;
+use super::{Instruction, IntcodeError};
+
+pub trait Hook {
+    /// Hooks that ignore every event set this to `false` so the
+    /// interpreter can skip gathering them.
+    const ENABLED: bool = true;
+
+    /// A memory read through a position or relative mode parameter.
+    fn read(&mut self, _addr: usize, _value: i64) {}
+
+    /// The value of an operand, whatever its mode, in the order the
+    /// instruction uses them. Follows the operand's `read`, if any.
+    fn operand(&mut self, _value: i64) {}
+
+    fn write(&mut self, _addr: usize, _old: i64, _new: i64) {}
+
+    fn relative_base(&mut self, _old: isize, _new: isize) {}
+
+    fn input(&mut self, _value: i64) {}
+
+    fn output(&mut self, _value: i64) {}
+
+    /// The instruction at `ip` finished executing; its reads, writes and
+    /// I/O have already been reported. Instructions that halt, block on
+    /// input or fault are not retired, though they may have reported reads.
+    fn retire(&mut self, _ip: usize, _instruction: &Instruction) {}
+
+    /// The instruction at `error.ip` faulted after reporting whatever it
+    /// did up to that point.
+    fn fault(&mut self, _error: &IntcodeError) {}
+}
+
+impl Hook for () {
+    const ENABLED: bool = false;
+}
+
+impl<H: Hook + ?Sized> Hook for &mut H {
+    const ENABLED: bool = H::ENABLED;
+
+    fn read(&mut self, addr: usize, value: i64) {
+        (**self).read(addr, value)
+    }
+
+    fn operand(&mut self, value: i64) {
+        (**self).operand(value)
+    }
+
+    fn write(&mut self, addr: usize, old: i64, new: i64) {
+        (**self).write(addr, old, new)
+    }
+
+    fn relative_base(&mut self, old: isize, new: isize) {
+        (**self).relative_base(old, new)
+    }
+
+    fn input(&mut self, value: i64) {
+        (**self).input(value)
+    }
+
+    fn output(&mut self, value: i64) {
+        (**self).output(value)
+    }
+
+    fn retire(&mut self, ip: usize, instruction: &Instruction) {
+        (**self).retire(ip, instruction)
+    }
+
+    fn fault(&mut self, error: &IntcodeError) {
+        (**self).fault(error)
+    }
+}
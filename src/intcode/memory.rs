@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+/// Storage backing an [`super::Intcode`] machine. Unwritten addresses read
+/// as zero.
+pub trait Memory {
+    fn get(&self, addr: usize) -> i64;
+
+    fn set(&mut self, addr: usize, val: i64);
+
+    /// One past the highest address that has been loaded or written.
+    fn end(&self) -> usize;
+
+    /// Every non-zero cell, in address order.
+    fn cells(&self) -> Vec<(usize, i64)>;
+}
+
+/// Memory backed by a hash map. Slower than [`DenseMemory`], but never
+/// allocates for addresses that are not used.
+#[derive(Clone, Debug, Default)]
+pub struct SparseMemory(HashMap<usize, i64>);
+
+/// Memory backed by a contiguous vector that grows as the program writes
+/// past its end. Writes far beyond the end of the vector go to a sparse
+/// overflow map instead, so a single stray write cannot force a huge
+/// allocation.
+#[derive(Clone, Debug, Default)]
+pub struct DenseMemory {
+    cells: Vec<i64>,
+    far: HashMap<usize, i64>,
+    /// One past the highest address loaded or written; `cells` may extend
+    /// further, since it grows in large steps.
+    end: usize,
+}
+
+/// How far past the current end a write may land and still grow the
+/// vector, beyond doubling its size.
+pub(super) const DENSE_SLACK: usize = 1 << 16;
+
+impl From<&[i64]> for SparseMemory {
+    fn from(s: &[i64]) -> Self {
+        Self(s.iter().copied().enumerate().collect())
+    }
+}
+
+impl Memory for SparseMemory {
+    fn get(&self, addr: usize) -> i64 {
+        match self.0.get(&addr) {
+            None => 0,
+            Some(n) => *n,
+        }
+    }
+
+    fn set(&mut self, addr: usize, val: i64) {
+        self.0.insert(addr, val);
+    }
+
+    fn end(&self) -> usize {
+        self.0.keys().max().map_or(0, |addr| addr + 1)
+    }
+
+    fn cells(&self) -> Vec<(usize, i64)> {
+        let mut cells: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(_, n)| **n != 0)
+            .map(|(addr, n)| (*addr, *n))
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+}
+
+impl From<&[i64]> for DenseMemory {
+    fn from(s: &[i64]) -> Self {
+        Self {
+            cells: s.to_vec(),
+            far: HashMap::new(),
+            end: s.len(),
+        }
+    }
+}
+
+impl Memory for DenseMemory {
+    #[inline]
+    fn get(&self, addr: usize) -> i64 {
+        match self.cells.get(addr) {
+            Some(n) => *n,
+            None if self.far.is_empty() => 0,
+            None => self.far.get(&addr).copied().unwrap_or(0),
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, addr: usize, val: i64) {
+        self.end = self.end.max(addr.saturating_add(1));
+        if let Some(cell) = self.cells.get_mut(addr) {
+            *cell = val;
+        } else if addr < self.cells.len() * 2 + DENSE_SLACK {
+            self.grow(addr + 1);
+            self.cells[addr] = val;
+        } else {
+            self.far.insert(addr, val);
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.end
+    }
+
+    fn cells(&self) -> Vec<(usize, i64)> {
+        let mut far: Vec<_> = self.far.iter().map(|(addr, n)| (*addr, *n)).collect();
+        far.sort_unstable();
+
+        self.cells
+            .iter()
+            .copied()
+            .enumerate()
+            .chain(far)
+            .filter(|(_, n)| *n != 0)
+            .collect()
+    }
+}
+
+impl DenseMemory {
+    fn grow(&mut self, len: usize) {
+        let len = len.max(self.cells.len() * 2);
+        self.cells.resize(len, 0);
+
+        if !self.far.is_empty() {
+            let moved: Vec<usize> = self.far.keys().copied().filter(|a| *a < len).collect();
+            for addr in moved {
+                self.cells[addr] = self.far.remove(&addr).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Intcode;
+
+    #[test]
+    fn dense_growth() {
+        let mut mem = DenseMemory::from(&[1, 2, 3][..]);
+        assert_eq!(mem.get(2), 3);
+        assert_eq!(mem.get(100), 0);
+        assert_eq!(mem.end(), 3);
+
+        mem.set(100, 7);
+        assert_eq!(mem.get(100), 7);
+        assert!(mem.far.is_empty());
+        assert!(mem.cells.len() > 100);
+
+        mem.set(usize::MAX / 2, -1);
+        assert_eq!(mem.get(usize::MAX / 2), -1);
+        assert_eq!(mem.far.len(), 1);
+        assert_eq!(mem.end(), usize::MAX / 2 + 1);
+        assert_eq!(
+            mem.cells(),
+            vec![(0, 1), (1, 2), (2, 3), (100, 7), (usize::MAX / 2, -1)]
+        );
+    }
+
+    #[test]
+    fn dense_absorbs_far_writes() {
+        let mut mem = DenseMemory::default();
+        mem.set(DENSE_SLACK + 10, 5);
+        assert_eq!(mem.far.len(), 1);
+
+        mem.set(DENSE_SLACK - 1, 1);
+        mem.set(DENSE_SLACK * 2, 2);
+        assert!(mem.far.is_empty());
+        assert_eq!(mem.get(DENSE_SLACK + 10), 5);
+        assert_eq!(mem.get(DENSE_SLACK * 2), 2);
+    }
+
+    #[test]
+    fn dense_end_ignores_slack() {
+        let program = [1101, 1, 2, 6, 99];
+        let mut dense = Intcode::with_memory(DenseMemory::from(&program[..]));
+        let mut sparse = Intcode::with_memory(SparseMemory::from(&program[..]));
+        dense.run();
+        sparse.run();
+
+        assert_eq!(dense.memory.end(), 7);
+        assert_eq!(dense.memory.end(), sparse.memory.end());
+        assert_eq!(dense.disassemble(), sparse.disassemble());
+        assert_eq!(dense.memory.cells(), sparse.memory.cells());
+    }
+
+    #[test]
+    fn sparse() {
+        let mut mem = SparseMemory::from(&[4, 5][..]);
+        assert_eq!(mem.end(), 2);
+        mem.set(1_000_000, 9);
+        assert_eq!(mem.get(1_000_000), 9);
+        assert_eq!(mem.get(999_999), 0);
+        assert_eq!(mem.end(), 1_000_001);
+        mem.set(0, 0);
+        assert_eq!(mem.cells(), vec![(1, 5), (1_000_000, 9)]);
+    }
+}
@@ -0,0 +1,272 @@
+//! Networks of Intcode machines whose outputs feed each other's inputs.
+//!
+//! ```
+//! # use adventofcode_2019::intcode::{Intcode, network::Network};
+//! // Each machine adds one to its input.
+//! let adder = Intcode::from("3,9,1001,9,1,9,4,9,99,0");
+//! let mut net = Network::pipeline(vec![adder.clone(), adder.clone(), adder]);
+//! net.seed(0, 10);
+//! assert_eq!(net.run().unwrap().output, Some(13));
+//! ```
+
+use std::fmt;
+
+use super::{DenseMemory, Intcode, IntcodeError, Memory, RunResult};
+
+pub type NodeId = usize;
+
+struct Node<M> {
+    vm: Intcode<M>,
+    targets: Vec<NodeId>,
+    halted: bool,
+    /// Blocked on input and nothing has arrived since.
+    waiting: bool,
+    last_output: Option<i64>,
+}
+
+pub struct Network<M = DenseMemory> {
+    nodes: Vec<Node<M>>,
+    last_halted: Option<NodeId>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum State {
+    /// Every machine halted.
+    Halted,
+    /// Some machines are still waiting for input nobody will send.
+    Quiescent,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub state: State,
+    /// The machine that halted last, if any did.
+    pub last_halted: Option<NodeId>,
+    /// The final value output by `last_halted`.
+    pub output: Option<i64>,
+}
+
+/// A fault in one of the machines of a network.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NetworkError {
+    pub node: NodeId,
+    pub error: IntcodeError,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {}: {}", self.node, self.error)
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl<M: Memory> Default for Network<M> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            last_halted: None,
+        }
+    }
+}
+
+impl<M: Memory> Network<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Machines connected one after the other, each feeding the next.
+    pub fn pipeline(vms: impl IntoIterator<Item = Intcode<M>>) -> Self {
+        let mut network = Self::new();
+        for vm in vms {
+            let id = network.add(vm);
+            if id > 0 {
+                network.connect(id - 1, id);
+            }
+        }
+        network
+    }
+
+    /// A pipeline whose last machine feeds back into the first.
+    pub fn ring(vms: impl IntoIterator<Item = Intcode<M>>) -> Self {
+        let mut network = Self::pipeline(vms);
+        if let Some(last) = network.nodes.len().checked_sub(1) {
+            network.connect(last, 0);
+        }
+        network
+    }
+
+    pub fn add(&mut self, vm: Intcode<M>) -> NodeId {
+        self.nodes.push(Node {
+            vm,
+            targets: Vec::new(),
+            halted: false,
+            waiting: false,
+            last_output: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Sends every output of `from` to the input of `to`. A machine may
+    /// feed several others; each receives a copy of every value. Outputs
+    /// of a machine without any connections stay in its output queue.
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        self.nodes[from].targets.push(to);
+    }
+
+    /// Queues an input value for a machine before (or between) runs.
+    pub fn seed(&mut self, node: NodeId, value: i64) {
+        self.nodes[node].vm.input(value);
+        self.nodes[node].waiting = false;
+    }
+
+    pub fn node(&self, id: NodeId) -> &Intcode<M> {
+        &self.nodes[id].vm
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Intcode<M> {
+        &mut self.nodes[id].vm
+    }
+
+    /// Runs the machines round-robin, each until it halts or blocks, and
+    /// forwards their output along the connections. Stops once every
+    /// machine has halted or none can make progress.
+    pub fn run(&mut self) -> Result<Outcome, NetworkError> {
+        loop {
+            let mut progress = false;
+
+            for id in 0..self.nodes.len() {
+                let node = &mut self.nodes[id];
+                if node.halted || node.waiting {
+                    continue;
+                }
+                progress = true;
+
+                let result = node
+                    .vm
+                    .try_run()
+                    .map_err(|error| NetworkError { node: id, error })?;
+
+                match result {
+                    RunResult::Halted => {
+                        node.halted = true;
+                        self.last_halted = Some(id);
+                    }
+                    _ => node.waiting = true,
+                }
+
+                if node.targets.is_empty() {
+                    node.last_output = node.vm.pending_output().last().or(node.last_output);
+                    continue;
+                }
+
+                let outputs: Vec<i64> = std::iter::from_fn(|| node.vm.output()).collect();
+                node.last_output = outputs.last().copied().or(node.last_output);
+
+                for target in self.nodes[id].targets.clone() {
+                    for value in &outputs {
+                        self.seed(target, *value);
+                    }
+                }
+            }
+
+            let state = if self.nodes.iter().all(|n| n.halted) {
+                State::Halted
+            } else if !progress {
+                State::Quiescent
+            } else {
+                continue;
+            };
+
+            return Ok(Outcome {
+                state,
+                last_halted: self.last_halted,
+                output: self.last_halted.and_then(|id| self.nodes[id].last_output),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::ErrorKind;
+
+    // Reads a value and outputs it plus one, then halts.
+    const INC: &str = "3,9,1001,9,1,9,4,9,99,0";
+
+    // Echoes its input until it reads a zero.
+    const ECHO: &str = "3,11,1006,11,10,4,11,1105,1,0,99,0";
+
+    #[test]
+    fn pipeline() {
+        let mut net = Network::pipeline((0..5).map(|_| Intcode::from(INC)));
+        net.seed(0, 1);
+        assert_eq!(
+            net.run(),
+            Ok(Outcome {
+                state: State::Halted,
+                last_halted: Some(4),
+                output: Some(6),
+            })
+        );
+    }
+
+    #[test]
+    fn ring() {
+        // Day 7 part 2 example, phase settings 9,8,7,6,5.
+        let program = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+                       27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+        let mut net = Network::ring([9, 8, 7, 6, 5].map(|phase| {
+            let mut vm = Intcode::from(program);
+            vm.input(phase);
+            vm
+        }));
+        net.seed(0, 0);
+        let outcome = net.run().unwrap();
+        assert_eq!(outcome.last_halted, Some(4));
+        assert_eq!(outcome.output, Some(139629729));
+    }
+
+    #[test]
+    fn fan_out_and_quiescence() {
+        let mut net = Network::new();
+        let source = net.add(Intcode::from(ECHO));
+        let a = net.add(Intcode::from(INC));
+        let b = net.add(Intcode::from(ECHO));
+        net.connect(source, a);
+        net.connect(source, b);
+
+        net.seed(source, 7);
+        net.seed(source, 8);
+        assert_eq!(
+            net.run(),
+            Ok(Outcome {
+                state: State::Quiescent,
+                last_halted: Some(a),
+                output: Some(8),
+            })
+        );
+        // b has no connections, so its output stays queued.
+        assert_eq!(net.node_mut(b).output(), Some(7));
+        assert_eq!(net.node_mut(b).output(), Some(8));
+
+        net.seed(source, 0);
+        net.seed(b, 0);
+        let outcome = net.run().unwrap();
+        assert_eq!(outcome.state, State::Halted);
+        assert_eq!(outcome.last_halted, Some(b));
+        assert_eq!(outcome.output, Some(8));
+    }
+
+    #[test]
+    fn faults() {
+        let mut net = Network::pipeline([Intcode::from(ECHO), Intcode::from("3,0,42")]);
+        net.seed(0, 5);
+        let err = net.run().unwrap_err();
+        assert_eq!(err.node, 1);
+        assert_eq!(err.error.ip, 2);
+        assert_eq!(err.error.kind, ErrorKind::UnknownOpcode(42));
+        assert_eq!(err.to_string(), format!("node 1: {}", err.error));
+    }
+}
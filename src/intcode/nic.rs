@@ -0,0 +1,411 @@
+//! Many Intcode machines exchanging packets concurrently.
+//!
+//! Every machine runs on its own thread behind a network interface (NIC).
+//! On boot a machine reads its own address. After that each input read
+//! takes the next queued packet as `x` then `y`, or `-1` if nothing is
+//! waiting, so machines never block on input. Machines send a packet by
+//! outputting three values: destination address, `x` and `y`.
+//!
+//! Machines run in slices of [`SLICE`] instructions and check between
+//! them whether the network is shutting down, so even one stuck in a loop
+//! that never reads input stops once [`NicNetwork::run`] returns.
+//!
+//! Packets are routed by the calling thread. Those sent to [`NAT_ADDR`] are
+//! held by the NAT, which remembers only the latest one. When every machine
+//! is idle — it has taken every packet routed to it and has since polled
+//! an empty queue at least twice without sending anything — the NAT sends
+//! its packet to address 0. Idleness is decided from packet counts rather
+//! than timing, so a given program always sees the same sequence of wake
+//! ups.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use super::network::NetworkError;
+use super::{InputSource, Intcode, OutputSink, RunResult};
+
+pub const NAT_ADDR: i64 = 255;
+
+/// Consecutive empty polls after which a machine counts as idle.
+const IDLE_POLLS: u32 = 2;
+
+/// Instructions a machine runs between checks for shutdown.
+pub const SLICE: u64 = 10_000;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Packet {
+    /// The sending machine, or `None` for the packet the NAT sends when
+    /// the network goes idle.
+    pub from: Option<usize>,
+    pub to: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Something the monitor passed to [`NicNetwork::run`] gets to see.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A machine sent a packet. Packets to addresses that are neither a
+    /// machine nor the NAT are dropped after being reported.
+    Packet(Packet),
+    /// The network went idle and the NAT sent its packet to address 0.
+    Wake(Packet),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The monitor asked to stop.
+    Stopped,
+    /// The network went idle with nothing for the NAT to send.
+    Idle,
+    /// Every machine halted.
+    Halted,
+}
+
+pub struct NicNetwork {
+    machines: Vec<Intcode>,
+}
+
+enum Message {
+    Packet(Packet),
+    Idle {
+        node: usize,
+        received: u64,
+    },
+    Exited {
+        node: usize,
+        result: Result<RunResult, NetworkError>,
+    },
+}
+
+struct Nic {
+    node: usize,
+    inbox: Receiver<(i64, i64)>,
+    router: Sender<Message>,
+    pending: VecDeque<i64>,
+    sending: Vec<i64>,
+    received: u64,
+    empty_polls: u32,
+}
+
+struct NicInput<'a>(&'a RefCell<Nic>);
+
+struct NicOutput<'a>(&'a RefCell<Nic>);
+
+impl InputSource for NicInput<'_> {
+    fn read(&mut self) -> Option<i64> {
+        let mut nic = self.0.borrow_mut();
+
+        if let Some(v) = nic.pending.pop_front() {
+            return Some(v);
+        }
+
+        match nic.inbox.try_recv() {
+            Ok((x, y)) => {
+                nic.received += 1;
+                nic.empty_polls = 0;
+                nic.pending.push_back(y);
+                Some(x)
+            }
+            Err(TryRecvError::Empty) => {
+                nic.empty_polls += 1;
+                if nic.empty_polls == IDLE_POLLS {
+                    let msg = Message::Idle {
+                        node: nic.node,
+                        received: nic.received,
+                    };
+                    nic.router.send(msg).ok();
+                }
+                thread::yield_now();
+                Some(-1)
+            }
+            // The network is shutting down.
+            Err(TryRecvError::Disconnected) => None,
+        }
+    }
+}
+
+impl OutputSink for NicOutput<'_> {
+    fn write(&mut self, value: i64) {
+        let mut nic = self.0.borrow_mut();
+        nic.empty_polls = 0;
+        nic.sending.push(value);
+
+        if let [to, x, y] = nic.sending[..] {
+            nic.sending.clear();
+            let packet = Packet {
+                from: Some(nic.node),
+                to,
+                x,
+                y,
+            };
+            nic.router.send(Message::Packet(packet)).ok();
+        }
+    }
+}
+
+impl NicNetwork {
+    /// Machine `i` gets address `i`.
+    pub fn new(machines: Vec<Intcode>) -> Self {
+        Self { machines }
+    }
+
+    /// Boots every machine and routes packets until the monitor returns
+    /// [`Control::Stop`], the network idles with nothing for the NAT to
+    /// send, or every machine halts.
+    pub fn run<F>(self, mut monitor: F) -> Result<Outcome, NetworkError>
+    where
+        F: FnMut(&Event) -> Control,
+    {
+        let n = self.machines.len();
+        let (router_tx, router_rx) = mpsc::channel();
+        let stopping = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            let mut inboxes = Vec::with_capacity(n);
+
+            for (node, mut vm) in self.machines.into_iter().enumerate() {
+                let (tx, rx) = mpsc::channel();
+                inboxes.push(Some(tx));
+
+                let nic = RefCell::new(Nic {
+                    node,
+                    inbox: rx,
+                    router: router_tx.clone(),
+                    pending: VecDeque::from([node as i64]),
+                    sending: Vec::new(),
+                    received: 0,
+                    empty_polls: 0,
+                });
+
+                let stopping = &stopping;
+                scope.spawn(move || {
+                    let (mut input, mut output) = (NicInput(&nic), NicOutput(&nic));
+                    let result = loop {
+                        match vm.run_bounded(&mut input, &mut output, Some(SLICE), None) {
+                            Ok(RunResult::BudgetExhausted) if !stopping.load(Ordering::Relaxed) => {
+                            }
+                            result => break result,
+                        }
+                    };
+                    let result = result.map_err(|error| NetworkError { node, error });
+                    let router = nic.borrow().router.clone();
+                    router.send(Message::Exited { node, result }).ok();
+                });
+            }
+            drop(router_tx);
+
+            let mut router = Router {
+                inboxes,
+                delivered: vec![0; n],
+                idle: vec![false; n],
+                nat: None,
+            };
+            let outcome = router.route(&router_rx, &mut monitor);
+
+            // Hanging up every inbox makes the machines' next empty poll
+            // report no input, which ends their threads; the flag ends
+            // those that are not polling.
+            router.inboxes.clear();
+            stopping.store(true, Ordering::Relaxed);
+            outcome
+        })
+    }
+}
+
+struct Router {
+    /// `None` once a machine has exited.
+    inboxes: Vec<Option<Sender<(i64, i64)>>>,
+    delivered: Vec<u64>,
+    idle: Vec<bool>,
+    nat: Option<Packet>,
+}
+
+impl Router {
+    fn route<F>(&mut self, rx: &Receiver<Message>, monitor: &mut F) -> Result<Outcome, NetworkError>
+    where
+        F: FnMut(&Event) -> Control,
+    {
+        while let Ok(msg) = rx.recv() {
+            match msg {
+                Message::Packet(packet) => {
+                    if let Some(from) = packet.from {
+                        self.idle[from] = false;
+                    }
+                    if monitor(&Event::Packet(packet)) == Control::Stop {
+                        return Ok(Outcome::Stopped);
+                    }
+
+                    if packet.to == NAT_ADDR {
+                        self.nat = Some(packet);
+                    } else {
+                        self.deliver(packet);
+                    }
+                }
+                Message::Idle { node, received } => {
+                    if received == self.delivered[node] {
+                        self.idle[node] = true;
+                    }
+                }
+                Message::Exited { node, result } => {
+                    result?;
+                    self.inboxes[node] = None;
+                    self.idle[node] = true;
+                    if self.inboxes.iter().all(Option::is_none) {
+                        return Ok(Outcome::Halted);
+                    }
+                }
+            }
+
+            if self.idle.iter().all(|idle| *idle) {
+                let Some(packet) = self.nat else {
+                    return Ok(Outcome::Idle);
+                };
+
+                let wake = Packet {
+                    from: None,
+                    to: 0,
+                    ..packet
+                };
+                if monitor(&Event::Wake(wake)) == Control::Stop {
+                    return Ok(Outcome::Stopped);
+                }
+                self.deliver(wake);
+            }
+        }
+
+        Ok(Outcome::Halted)
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        let Ok(to) = usize::try_from(packet.to) else {
+            return;
+        };
+
+        if let Some(Some(inbox)) = self.inboxes.get(to) {
+            if inbox.send((packet.x, packet.y)).is_ok() {
+                self.delivered[to] += 1;
+                self.idle[to] = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{asm, ErrorKind};
+
+    /// Node 0 starts a packet around the ring; every node passes it on to
+    /// the next with `y` incremented, until `y` reaches 10 and it goes to
+    /// the NAT instead.
+    fn relay(nodes: i64) -> Vec<Intcode> {
+        let program = asm::assemble(&format!(
+            "       in [addr]
+                    jnz [addr], #loop
+                    out #1
+                    out #7
+                    out #0
+             loop:  in [x]
+                    eq [x], #-1, [t]
+                    jnz [t], #loop
+                    in [y]
+                    lt [y], #10, [t]
+                    jz [t], #nat
+                    add [addr], #1, [dest]
+                    eq [dest], #{nodes}, [t]
+                    jz [t], #send
+                    add #0, #0, [dest]
+             send:  add [y], #1, [y]
+                    out [dest]
+                    out [x]
+                    out [y]
+                    jnz #1, #loop
+             nat:   out #255
+                    out [x]
+                    out [y]
+                    jnz #1, #loop
+             addr:  .data 0
+             x:     .data 0
+             y:     .data 0
+             t:     .data 0
+             dest:  .data 0"
+        ))
+        .unwrap();
+
+        (0..nodes).map(|_| Intcode::from(&program[..])).collect()
+    }
+
+    #[test]
+    fn relay_with_nat() {
+        let mut events = Vec::new();
+        let mut last_wake = None;
+
+        let outcome = NicNetwork::new(relay(3))
+            .run(|event| {
+                events.push(match event {
+                    Event::Packet(p) => (p.from.unwrap() as i64, p.to, p.y),
+                    Event::Wake(p) => (NAT_ADDR, p.to, p.y),
+                });
+
+                match event {
+                    Event::Wake(p) if last_wake.replace(p.y) == Some(p.y) => Control::Stop,
+                    _ => Control::Continue,
+                }
+            })
+            .unwrap();
+
+        assert_eq!(outcome, Outcome::Stopped);
+
+        let mut expected: Vec<(i64, i64, i64)> =
+            (0..=10).map(|y| (y % 3, (y + 1) % 3, y)).collect();
+        expected.extend([
+            (2, NAT_ADDR, 10),
+            (NAT_ADDR, 0, 10),
+            (0, NAT_ADDR, 10),
+            (NAT_ADDR, 0, 10),
+        ]);
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn idle_without_nat_packet() {
+        let outcome = NicNetwork::new(relay(3)[1..].to_vec()).run(|_| Control::Continue);
+        assert_eq!(outcome, Ok(Outcome::Idle));
+    }
+
+    #[test]
+    fn stops_busy_machines() {
+        // One machine sends a packet and halts, the other spins forever
+        // without polling for input.
+        let machines = vec![
+            Intcode::from("104,1,104,2,104,3,99"),
+            Intcode::from("1105,1,0"),
+        ];
+        let outcome = NicNetwork::new(machines).run(|_| Control::Stop);
+        assert_eq!(outcome, Ok(Outcome::Stopped));
+    }
+
+    #[test]
+    fn halts_and_faults() {
+        let machines = vec![Intcode::from("3,0,99"), Intcode::from("3,0,99")];
+        let outcome = NicNetwork::new(machines).run(|_| Control::Continue);
+        assert_eq!(outcome, Ok(Outcome::Halted));
+
+        let machines = vec![Intcode::from("3,0,99"), Intcode::from("3,0,42")];
+        let err = NicNetwork::new(machines)
+            .run(|_| Control::Continue)
+            .unwrap_err();
+        assert_eq!(err.node, 1);
+        assert_eq!(err.error.kind, ErrorKind::UnknownOpcode(42));
+    }
+}
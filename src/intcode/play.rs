@@ -0,0 +1,349 @@
+//! Playing ASCII programs, such as text adventures, from a terminal.
+//!
+//! A [`Session`] shows the program's output and sends it each line typed
+//! as a command. Lines starting with `/` are handled by the session
+//! instead:
+//!
+//! ```text
+//! /save FILE      save the machine in the `state` format
+//! /load FILE      restore a machine saved with /save
+//! /history        list the commands sent so far
+//! /help           show this list
+//! /quit           leave
+//! ```
+//!
+//! `!!` repeats the last command and `!N` the N-th one from `/history`.
+//!
+//! A session can also log a transcript: each line of program output
+//! prefixed with `| ` and each command sent with `> `. [`replay`] plays a
+//! transcript back against a program and checks it still prints the same
+//! thing, which makes transcripts usable as regression tests. Blank lines
+//! and lines starting with `#` in a transcript are ignored.
+
+use std::io::{self, BufRead, Write};
+
+use super::{Intcode, RunResult};
+
+const HELP: &str = "\
+/save FILE      save the machine
+/load FILE      restore a saved machine
+/history        list the commands sent so far
+/help           show this list
+/quit           leave
+!!, !N          repeat the last or N-th command";
+
+pub struct Session {
+    vm: Intcode,
+    history: Vec<String>,
+    transcript: Option<Box<dyn Write>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Replay {
+    Passed {
+        commands: usize,
+    },
+    /// The output before command number `command` (counting from 0, or
+    /// the number of commands for the output after the last one) differs.
+    Failed {
+        command: usize,
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+}
+
+impl Session {
+    pub fn new(vm: Intcode) -> Self {
+        Self {
+            vm,
+            history: Vec::new(),
+            transcript: None,
+        }
+    }
+
+    /// Logs a transcript of everything from here on to `w`.
+    pub fn log_to(&mut self, w: impl Write + 'static) {
+        self.transcript = Some(Box::new(w));
+    }
+
+    pub fn machine(&self) -> &Intcode {
+        &self.vm
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Runs the program until it needs input or stops, and returns what it
+    /// printed. Values outside the ASCII range appear as numbers on their
+    /// own line. A fault is reported as the program's last line of output
+    /// and stops it for good.
+    pub fn advance(&mut self) -> io::Result<(String, Option<RunResult>)> {
+        let result = self.vm.try_run();
+        let mut text = String::new();
+
+        while let Some(value) = self.vm.output() {
+            match u8::try_from(value).ok().filter(u8::is_ascii) {
+                Some(c) => text.push(char::from(c)),
+                None => {
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    text.push_str(&format!("{value}\n"));
+                }
+            }
+        }
+
+        let result = match result {
+            Ok(result) => Some(result),
+            Err(e) => {
+                text.push_str(&format!("error: {e}\n"));
+                None
+            }
+        };
+
+        if let Some(t) = &mut self.transcript {
+            for line in text.lines() {
+                writeln!(t, "| {line}")?;
+            }
+            t.flush()?;
+        }
+
+        Ok((text, result))
+    }
+
+    /// Sends `command` to the program as a line of input.
+    pub fn send(&mut self, command: &str) -> io::Result<()> {
+        self.vm.input_line(command);
+        self.history.push(command.to_string());
+
+        if let Some(t) = &mut self.transcript {
+            writeln!(t, "> {command}")?;
+            t.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Plays the program interactively until it halts, faults, `/quit` is
+    /// entered or `input` runs out.
+    pub fn play<R: BufRead, W: Write>(&mut self, mut input: R, mut out: W) -> io::Result<()> {
+        loop {
+            let (text, result) = self.advance()?;
+            write!(out, "{text}")?;
+
+            match result {
+                Some(RunResult::BlockedOnInput) => {}
+                Some(_) => return writeln!(out, "[halted]"),
+                None => return Ok(()),
+            }
+
+            loop {
+                out.flush()?;
+                let mut line = String::new();
+                if input.read_line(&mut line)? == 0 {
+                    return Ok(());
+                }
+                let line = line.trim_end_matches(['\r', '\n']);
+
+                if let Some(meta) = line.strip_prefix('/') {
+                    let mut words = meta.split_whitespace();
+                    match (words.next(), words.next()) {
+                        (Some("save"), Some(path)) => match self.vm.save(path) {
+                            Ok(()) => writeln!(out, "[saved to {path}]")?,
+                            Err(e) => writeln!(out, "[cannot save: {e}]")?,
+                        },
+                        (Some("load"), Some(path)) => match Intcode::load(path) {
+                            Ok(vm) => {
+                                self.vm = vm;
+                                writeln!(out, "[loaded {path}]")?;
+                                break;
+                            }
+                            Err(e) => writeln!(out, "[cannot load: {e}]")?,
+                        },
+                        (Some("history"), None) => {
+                            for (i, command) in self.history.iter().enumerate() {
+                                writeln!(out, "{:>4}  {command}", i + 1)?;
+                            }
+                        }
+                        (Some("help"), None) => writeln!(out, "{HELP}")?,
+                        (Some("quit"), None) => return Ok(()),
+                        _ => writeln!(out, "[unknown command, try /help]")?,
+                    }
+                    continue;
+                }
+
+                let command = match line.strip_prefix('!') {
+                    Some("!") => self.history.last(),
+                    Some(n) => n
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| self.history.get(n.wrapping_sub(1))),
+                    None => {
+                        self.send(line)?;
+                        break;
+                    }
+                };
+
+                match command.cloned() {
+                    Some(command) => {
+                        writeln!(out, "{command}")?;
+                        self.send(&command)?;
+                        break;
+                    }
+                    None => writeln!(out, "[no such command in history]")?,
+                }
+            }
+        }
+    }
+}
+
+/// Plays the commands of a transcript against `vm`, checking the output
+/// before each command and after the last one.
+pub fn replay<R: BufRead>(vm: Intcode, transcript: R) -> io::Result<Replay> {
+    let mut segments = vec![(Vec::new(), None)];
+
+    for (n, line) in transcript.lines().enumerate() {
+        let line = line?;
+        if let Some(text) = line
+            .strip_prefix("| ")
+            .or(line.strip_prefix('|').filter(|t| t.is_empty()))
+        {
+            segments.last_mut().unwrap().0.push(text.to_string());
+        } else if let Some(command) = line.strip_prefix("> ") {
+            segments.last_mut().unwrap().1 = Some(command.to_string());
+            segments.push((Vec::new(), None));
+        } else if !line.trim().is_empty() && !line.starts_with('#') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: expected '| ' or '> '", n + 1),
+            ));
+        }
+    }
+
+    let mut session = Session::new(vm);
+    for (command, (expected, send)) in segments.into_iter().enumerate() {
+        let (text, _) = session.advance()?;
+        let actual: Vec<String> = text.lines().map(String::from).collect();
+        if actual != expected {
+            return Ok(Replay::Failed {
+                command,
+                expected,
+                actual,
+            });
+        }
+
+        match send {
+            Some(line) => session.send(&line)?,
+            None => return Ok(Replay::Passed { commands: command }),
+        }
+    }
+
+    unreachable!("the last segment has no command")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::echo;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A transcript sink the test can still read afterwards.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn play_and_replay() {
+        let log = Shared::default();
+        let mut session = Session::new(echo());
+        session.log_to(log.clone());
+
+        let mut out = Vec::new();
+        session
+            .play(
+                "hello\n/history\n!1\n!9\n/bogus\nbyeq\n".as_bytes(),
+                &mut out,
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "? hello\n\
+             ?    1  hello\n\
+             hello\n\
+             hello\n\
+             ? [no such command in history]\n\
+             [unknown command, try /help]\n\
+             bye\n\
+             1000000\n\
+             [halted]\n"
+        );
+        assert_eq!(session.history(), ["hello", "hello", "byeq"]);
+
+        let transcript = String::from_utf8(log.0.borrow().clone()).unwrap();
+        assert_eq!(
+            transcript,
+            "| ? \n\
+             > hello\n\
+             | hello\n\
+             | ? \n\
+             > hello\n\
+             | hello\n\
+             | ? \n\
+             > byeq\n\
+             | bye\n\
+             | 1000000\n"
+        );
+
+        assert_eq!(
+            replay(echo(), transcript.as_bytes()).unwrap(),
+            Replay::Passed { commands: 3 }
+        );
+
+        let changed = transcript.replace("| bye", "| bye!");
+        assert_eq!(
+            replay(echo(), changed.as_bytes()).unwrap(),
+            Replay::Failed {
+                command: 3,
+                expected: vec!["bye!".to_string(), "1000000".to_string()],
+                actual: vec!["bye".to_string(), "1000000".to_string()],
+            }
+        );
+
+        assert!(replay(echo(), "# comment\n\nbogus\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("intcode-play-{}", std::process::id()));
+        let path = path.display();
+
+        let mut session = Session::new(echo());
+        let mut out = Vec::new();
+        let script = format!("one\n/save {path}\ntwo\n/load {path}\nthree\n/quit\n");
+        session.play(script.as_bytes(), &mut out).unwrap();
+        std::fs::remove_file(path.to_string()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "? one\n\
+                 ? [saved to {path}]\n\
+                 two\n\
+                 ? [loaded {path}]\n\
+                 three\n\
+                 ? "
+            )
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Counting where a program spends its time.
+//!
+//! ```
+//! # use adventofcode_2019::intcode::{Intcode, profile::Profiler};
+//! let mut intcode = Intcode::from("1101,2,3,5,99,0");
+//! let mut profiler = Profiler::new();
+//! intcode.try_run_hooked(&mut profiler).unwrap();
+//! assert_eq!(profiler.total(), 1);
+//! print!("{profiler}");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::hash::Hash;
+
+use super::{Hook, Instruction, IntcodeError, Opcode, ParameterMode};
+
+/// Rows per section in the text report; the CSV report has all of them.
+const TOP: usize = 20;
+
+#[derive(Debug, Default, Clone)]
+pub struct Profiler {
+    total: u64,
+    opcodes: HashMap<Opcode, u64>,
+    /// Count and the opcode last executed there.
+    addresses: HashMap<usize, (u64, Opcode)>,
+    modes: HashMap<(Opcode, Vec<ParameterMode>), u64>,
+    reads: HashMap<usize, u64>,
+    writes: HashMap<usize, u64>,
+    /// Reads of the instruction in flight, counted once it retires.
+    pending: Vec<usize>,
+}
+
+impl Hook for Profiler {
+    fn read(&mut self, addr: usize, _value: i64) {
+        self.pending.push(addr);
+    }
+
+    fn write(&mut self, addr: usize, _old: i64, _new: i64) {
+        *self.writes.entry(addr).or_default() += 1;
+    }
+
+    fn retire(&mut self, ip: usize, instruction: &Instruction) {
+        for addr in self.pending.drain(..) {
+            *self.reads.entry(addr).or_default() += 1;
+        }
+
+        let opcode = instruction.opcode();
+        self.total += 1;
+        *self.opcodes.entry(opcode).or_default() += 1;
+
+        let at = self.addresses.entry(ip).or_insert((0, opcode));
+        at.0 += 1;
+        at.1 = opcode;
+
+        *self
+            .modes
+            .entry((opcode, instruction.modes().to_vec()))
+            .or_default() += 1;
+    }
+
+    fn fault(&mut self, _error: &IntcodeError) {
+        self.pending.clear();
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instructions executed while profiling.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Executions per opcode, most frequent first.
+    pub fn opcodes(&self) -> Vec<(Opcode, u64)> {
+        sorted(&self.opcodes)
+    }
+
+    /// Executions per instruction address, most frequent first.
+    pub fn addresses(&self) -> Vec<(usize, Opcode, u64)> {
+        let mut v: Vec<_> = self
+            .addresses
+            .iter()
+            .map(|(addr, (n, op))| (*addr, *op, *n))
+            .collect();
+        v.sort_by_key(|(addr, _, n)| (std::cmp::Reverse(*n), *addr));
+        v
+    }
+
+    /// Executions per opcode and parameter mode combination, e.g.
+    /// `add rel,imm,pos`, most frequent first.
+    pub fn modes(&self) -> Vec<(String, u64)> {
+        let mut v: Vec<_> = self
+            .modes
+            .iter()
+            .map(|((op, modes), n)| (mode_key(*op, modes), *n))
+            .collect();
+        v.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+        v
+    }
+
+    /// Reads per memory address, most frequent first. Immediate operands
+    /// are part of the instruction and do not count as reads.
+    pub fn reads(&self) -> Vec<(usize, u64)> {
+        sorted(&self.reads)
+    }
+
+    /// Writes per memory address, most frequent first.
+    pub fn writes(&self) -> Vec<(usize, u64)> {
+        sorted(&self.writes)
+    }
+
+    /// Every counter as `section,key,count` rows under a header line.
+    pub fn csv(&self) -> String {
+        let mut out = String::from("section,key,count\n");
+        for (op, n) in self.opcodes() {
+            writeln!(out, "opcode,{},{n}", op.mnemonic()).unwrap();
+        }
+        for (addr, op, n) in self.addresses() {
+            writeln!(out, "address,{addr} {},{n}", op.mnemonic()).unwrap();
+        }
+        for (key, n) in self.modes() {
+            writeln!(out, "modes,{},{n}", key.replace(',', " ")).unwrap();
+        }
+        for (addr, n) in self.reads() {
+            writeln!(out, "read,{addr},{n}").unwrap();
+        }
+        for (addr, n) in self.writes() {
+            writeln!(out, "write,{addr},{n}").unwrap();
+        }
+        out
+    }
+
+    fn percent(&self, n: u64) -> f64 {
+        100.0 * n as f64 / self.total.max(1) as f64
+    }
+}
+
+/// The text report, showing the busiest entries of each section.
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "instructions executed: {}", self.total)?;
+
+        writeln!(f, "\n{:<16} {:>12} {:>7}", "opcode", "count", "%")?;
+        for (op, n) in self.opcodes().into_iter().take(TOP) {
+            writeln!(f, "{:<16} {n:>12} {:>7.2}", op.mnemonic(), self.percent(n))?;
+        }
+
+        writeln!(f, "\n{:<16} {:>12} {:>7}", "address", "count", "%")?;
+        for (addr, op, n) in self.addresses().into_iter().take(TOP) {
+            let key = format!("{addr:>5} {}", op.mnemonic());
+            writeln!(f, "{key:<16} {n:>12} {:>7.2}", self.percent(n))?;
+        }
+
+        writeln!(f, "\n{:<16} {:>12} {:>7}", "modes", "count", "%")?;
+        for (key, n) in self.modes().into_iter().take(TOP) {
+            writeln!(f, "{key:<16} {n:>12} {:>7.2}", self.percent(n))?;
+        }
+
+        for (title, heat) in [("reads", self.reads()), ("writes", self.writes())] {
+            writeln!(f, "\n{title:<16} {:>12}", "count")?;
+            for (addr, n) in heat.into_iter().take(TOP) {
+                writeln!(f, "{addr:<16} {n:>12}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn sorted<K: Copy + Ord + Hash>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+    let mut v: Vec<_> = counts.iter().map(|(k, n)| (*k, *n)).collect();
+    v.sort_by_key(|(k, n)| (std::cmp::Reverse(*n), *k));
+    v
+}
+
+fn mode_key(opcode: Opcode, modes: &[ParameterMode]) -> String {
+    let names: Vec<_> = modes
+        .iter()
+        .map(|m| match m {
+            ParameterMode::Position => "pos",
+            ParameterMode::Immediate => "imm",
+            ParameterMode::Relative => "rel",
+        })
+        .collect();
+    format!("{} {}", opcode.mnemonic(), names.join(","))
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::QUINE;
+    use crate::intcode::Intcode;
+
+    fn profile(program: &str) -> Profiler {
+        let mut intcode = Intcode::from(program);
+        let mut profiler = Profiler::new();
+        intcode.try_run_hooked(&mut profiler).unwrap();
+        assert_eq!(profiler.total(), intcode.cycles());
+        profiler
+    }
+
+    #[test]
+    fn counts() {
+        let p = profile(QUINE);
+        assert_eq!(p.total(), 80);
+        assert_eq!(
+            p.opcodes(),
+            vec![
+                (Opcode::Add, 16),
+                (Opcode::Output, 16),
+                (Opcode::JumpIfFalse, 16),
+                (Opcode::Equals, 16),
+                (Opcode::RelativeBase, 16),
+            ]
+        );
+        assert_eq!(
+            p.addresses(),
+            vec![
+                (0, Opcode::RelativeBase, 16),
+                (2, Opcode::Output, 16),
+                (4, Opcode::Add, 16),
+                (8, Opcode::Equals, 16),
+                (12, Opcode::JumpIfFalse, 16),
+            ]
+        );
+        assert_eq!(
+            p.modes(),
+            vec![
+                ("add pos,imm,pos".to_string(), 16),
+                ("arb imm".to_string(), 16),
+                ("eq pos,imm,pos".to_string(), 16),
+                ("jz pos,imm".to_string(), 16),
+                ("out rel".to_string(), 16),
+            ]
+        );
+
+        // [100] is read by the add and the eq, [101] by the jz, and the
+        // output reads each word of the program once.
+        let reads = p.reads();
+        assert_eq!(reads[..2], [(100, 32), (101, 16)]);
+        assert_eq!(reads.len(), 18);
+        assert_eq!(p.writes(), vec![(100, 16), (101, 16)]);
+    }
+
+    #[test]
+    fn reports() {
+        let p = profile("1101,2,3,5,99,0");
+
+        assert_eq!(
+            p.csv(),
+            "section,key,count\n\
+             opcode,add,1\n\
+             address,0 add,1\n\
+             modes,add imm imm pos,1\n\
+             write,5,1\n"
+        );
+
+        let text = p.to_string();
+        assert!(text.starts_with("instructions executed: 1\n"));
+        assert!(text.contains("add                         1  100.00\n"));
+        assert!(text.contains("    0 add                   1  100.00\n"));
+    }
+
+    #[test]
+    fn fault_does_not_count_reads() {
+        // Reads [5], then faults reading [-1].
+        let mut intcode = Intcode::from("1,5,-1,0,99,7");
+        let mut profiler = Profiler::new();
+        assert!(intcode.try_run_hooked(&mut profiler).is_err());
+        assert_eq!(profiler.total(), 0);
+        assert_eq!(profiler.reads(), vec![]);
+
+        intcode.set_mem(2, 5);
+        intcode.try_run_hooked(&mut profiler).unwrap();
+        assert_eq!(profiler.reads(), vec![(5, 2)]);
+    }
+}
@@ -0,0 +1,360 @@
+//! Saving and loading paused machines.
+//!
+//! A save state is a small line-oriented text file:
+//!
+//! ```text
+//! intcode-state 1
+//! size 1024
+//! ip 42
+//! rb -3
+//! cycles 1875
+//! input 1,2,3
+//! output 10
+//! mem 0 1101,5,0,7,99
+//! mem 1000 -1,0,4
+//! ```
+//!
+//! The first line names the format and its version; readers reject any
+//! version they do not know. `size` is one past the highest address in use
+//! (see [`Memory::end`]), `ip` and `rb` are the registers, `cycles` is
+//! [`Intcode::cycles`], and `input` / `output` hold the pending queues
+//! front first, possibly empty. Each `mem` line gives a start address
+//! followed by the consecutive words stored from there; addresses not
+//! covered by any `mem` line are zero. Every field but `mem` appears
+//! exactly once.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use super::{Intcode, Memory, Snapshot};
+
+const MAGIC: &str = "intcode-state";
+const VERSION: u32 = 1;
+
+/// Runs of zeros up to this long are written inline rather than starting a
+/// new `mem` line.
+const MAX_GAP: usize = 8;
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    Format { line: usize, message: String },
+    Missing(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Io(e) => write!(f, "{e}"),
+            StateError::Format { line, message } => write!(f, "line {line}: {message}"),
+            StateError::Missing(field) => write!(f, "missing {field}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> Self {
+        StateError::Io(e)
+    }
+}
+
+impl<M: Memory> Snapshot<M> {
+    pub fn write_to<W: Write>(&self, w: W) -> io::Result<()> {
+        write_state(
+            w,
+            &self.memory,
+            self.ip,
+            self.rb,
+            self.cycles,
+            self.input(),
+            self.output(),
+        )
+    }
+}
+
+impl<M: Memory + Default> Snapshot<M> {
+    pub fn read_from<R: BufRead>(r: R) -> Result<Self, StateError> {
+        let mut lines = r.lines().enumerate();
+        let err = |line: usize, message: String| StateError::Format {
+            line: line + 1,
+            message,
+        };
+
+        match lines.next() {
+            Some((_, header)) => {
+                let header = header?;
+                let version = header
+                    .strip_prefix(MAGIC)
+                    .and_then(|v| v.strip_prefix(' '))
+                    .and_then(|v| v.parse::<u32>().ok())
+                    .ok_or_else(|| err(0, format!("not an {MAGIC} file")))?;
+                if version != VERSION {
+                    return Err(err(0, format!("unsupported version {version}")));
+                }
+            }
+            None => return Err(err(0, "empty file".to_string())),
+        }
+
+        let mut memory = M::default();
+        let mut size = None;
+        let mut ip = None;
+        let mut rb = None;
+        let mut cycles = None;
+        let mut input = Default::default();
+        let mut output = Default::default();
+        let mut seen = BTreeSet::new();
+
+        for (n, line) in lines {
+            let line = line?;
+            let (key, rest) = line.split_once(' ').unwrap_or((&line, ""));
+            if !matches!(key, "" | "mem") && !seen.insert(key.to_string()) {
+                return Err(err(n, format!("duplicate field '{key}'")));
+            }
+            let parse_one = |s: &str| {
+                s.trim()
+                    .parse::<i64>()
+                    .map_err(|_| err(n, format!("invalid {key} '{s}'")))
+            };
+            let parse_list = |s: &str| -> Result<Vec<i64>, StateError> {
+                s.split(',')
+                    .filter(|w| !w.trim().is_empty())
+                    .map(parse_one)
+                    .collect()
+            };
+            let parse_addr = |s: &str| {
+                s.trim()
+                    .parse::<usize>()
+                    .map_err(|_| err(n, format!("invalid {key} '{s}'")))
+            };
+
+            match key {
+                "" => continue,
+                "size" => size = Some(parse_addr(rest)?),
+                "ip" => ip = Some(parse_addr(rest)?),
+                "rb" => rb = Some(parse_one(rest)? as isize),
+                "cycles" => {
+                    let count = rest.trim().parse::<u64>();
+                    cycles = Some(count.map_err(|_| err(n, format!("invalid cycles '{rest}'")))?);
+                }
+                "input" => input = parse_list(rest)?.into(),
+                "output" => output = parse_list(rest)?.into(),
+                "mem" => {
+                    let (start, words) = rest.split_once(' ').unwrap_or((rest, ""));
+                    let start = parse_addr(start)?;
+                    for (i, word) in parse_list(words)?.into_iter().enumerate() {
+                        let addr = start
+                            .checked_add(i)
+                            .ok_or_else(|| err(n, "mem runs past the end of memory".to_string()))?;
+                        memory.set(addr, word);
+                    }
+                }
+                other => return Err(err(n, format!("unknown field '{other}'"))),
+            }
+        }
+
+        let size = size.ok_or(StateError::Missing("size"))?;
+        if memory.end() < size {
+            memory.set(size - 1, 0);
+        }
+
+        Ok(Snapshot {
+            memory,
+            ip: ip.ok_or(StateError::Missing("ip"))?,
+            rb: rb.ok_or(StateError::Missing("rb"))?,
+            input,
+            output,
+            cycles: cycles.ok_or(StateError::Missing("cycles"))?,
+        })
+    }
+}
+
+impl<M: Memory> Intcode<M> {
+    /// Writes the machine's full state to `path`, see the [module
+    /// documentation](self) for the format.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        write_state(
+            &mut w,
+            &self.memory,
+            self.ip,
+            self.rb,
+            self.cycles,
+            self.pending_input(),
+            self.pending_output(),
+        )?;
+        w.flush()
+    }
+}
+
+impl<M: Memory + Default> Intcode<M> {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StateError> {
+        let r = BufReader::new(File::open(path)?);
+        Ok(Self::from(Snapshot::read_from(r)?))
+    }
+}
+
+fn write_state<M: Memory, W: Write>(
+    mut w: W,
+    memory: &M,
+    ip: usize,
+    rb: isize,
+    cycles: u64,
+    input: impl Iterator<Item = i64>,
+    output: impl Iterator<Item = i64>,
+) -> io::Result<()> {
+    writeln!(w, "{MAGIC} {VERSION}")?;
+    writeln!(w, "size {}", memory.end())?;
+    writeln!(w, "ip {ip}")?;
+    writeln!(w, "rb {rb}")?;
+    writeln!(w, "cycles {cycles}")?;
+    writeln!(w, "input {}", join(input))?;
+    writeln!(w, "output {}", join(output))?;
+
+    let mut segments: Vec<(usize, Vec<i64>)> = Vec::new();
+    for (addr, n) in memory.cells() {
+        match segments.last_mut() {
+            Some((start, words)) if addr - (*start + words.len()) <= MAX_GAP => {
+                words.resize(addr - *start, 0);
+                words.push(n);
+            }
+            _ => segments.push((addr, vec![n])),
+        }
+    }
+
+    for (start, words) in segments {
+        writeln!(w, "mem {start} {}", join(words.into_iter()))?;
+    }
+
+    Ok(())
+}
+
+fn join(words: impl Iterator<Item = i64>) -> String {
+    words.map(|n| n.to_string()).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{DenseMemory, RunResult, SparseMemory};
+
+    // Reads a value, stores it far away, reads another, outputs the sum.
+    const PROGRAM: &str = "109,-2,203,1000002,3,20,1,1000000,20,21,4,21,99";
+
+    fn paused() -> Intcode {
+        let mut intcode = Intcode::from(PROGRAM);
+        intcode.input(30);
+        assert_eq!(intcode.run(), RunResult::BlockedOnInput);
+        intcode
+    }
+
+    #[test]
+    fn format() {
+        let mut intcode = paused();
+        intcode.output.push_back(-5);
+        let mut buf = Vec::new();
+        intcode.snapshot().write_to(&mut buf).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "intcode-state 1\n\
+             size 1000001\n\
+             ip 4\n\
+             rb -2\n\
+             cycles 2\n\
+             input \n\
+             output -5\n\
+             mem 0 109,-2,203,1000002,3,20,1,1000000,20,21,4,21,99\n\
+             mem 1000000 30\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut intcode = paused();
+        intcode.input(12);
+        intcode.input(99);
+
+        let mut buf = Vec::new();
+        intcode.snapshot().write_to(&mut buf).unwrap();
+        let snapshot = Snapshot::<SparseMemory>::read_from(&buf[..]).unwrap();
+        assert_eq!(snapshot.ip(), 4);
+        assert_eq!(snapshot.rb(), -2);
+        assert_eq!(snapshot.cycles(), 2);
+        assert_eq!(snapshot.memory().end(), intcode.memory.end());
+        assert_eq!(snapshot.input().collect::<Vec<_>>(), vec![12, 99]);
+
+        let mut resumed = Intcode::from(snapshot);
+        assert_eq!(resumed.run(), RunResult::Halted);
+        assert_eq!(resumed.output(), Some(42));
+        assert_eq!(resumed.pending_input().collect::<Vec<_>>(), vec![99]);
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("intcode-state-{}", std::process::id()));
+        paused().save(&path).unwrap();
+
+        let mut resumed = Intcode::<DenseMemory>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        resumed.input(5);
+        resumed.run();
+        assert_eq!(resumed.output(), Some(35));
+    }
+
+    #[test]
+    fn errors() {
+        let load = |s: &str| Snapshot::<DenseMemory>::read_from(s.as_bytes()).unwrap_err();
+
+        assert_eq!(load("").to_string(), "line 1: empty file");
+        assert_eq!(
+            load("hello\n").to_string(),
+            "line 1: not an intcode-state file"
+        );
+        assert_eq!(
+            load("intcode-state 2\n").to_string(),
+            "line 1: unsupported version 2"
+        );
+        assert_eq!(
+            load("intcode-state 1\nsize 1\nip x\n").to_string(),
+            "line 3: invalid ip 'x'"
+        );
+        assert_eq!(
+            load("intcode-state 1\nmem 0 1,2,three\n").to_string(),
+            "line 2: invalid mem 'three'"
+        );
+        assert_eq!(
+            load("intcode-state 1\nmem 18446744073709551615 1,2\n").to_string(),
+            "line 2: mem runs past the end of memory"
+        );
+        assert_eq!(
+            load("intcode-state 1\nflags 3\n").to_string(),
+            "line 2: unknown field 'flags'"
+        );
+        assert_eq!(
+            load("intcode-state 1\nsize 1\nip 0\n").to_string(),
+            "missing rb"
+        );
+        assert_eq!(
+            load("intcode-state 1\nsize 1\nip 0\nrb 0\n").to_string(),
+            "missing cycles"
+        );
+        assert_eq!(
+            load("intcode-state2\n").to_string(),
+            "line 1: not an intcode-state file"
+        );
+        assert_eq!(
+            load("intcode-state  1\n").to_string(),
+            "line 1: not an intcode-state file"
+        );
+        assert_eq!(
+            load("intcode-state 1\nip 0\nip 4\n").to_string(),
+            "line 3: duplicate field 'ip'"
+        );
+    }
+}
@@ -0,0 +1,567 @@
+//! Recording every executed instruction and checking a machine against a
+//! recording.
+//!
+//! A trace is a text file with one line per retired instruction under an
+//! `intcode-trace 1` header:
+//!
+//! ```text
+//! intcode-trace 1
+//! 0 109 v1 b1
+//! 2 204 r0=109 v109 o109
+//! 4 1001 r100=0 v0 v1 w100=1
+//! ```
+//!
+//! Each line holds the `ip` and the instruction word, followed by the
+//! memory reads (`r`addr`=`value) it made, the value of every operand in
+//! order whatever its mode (`v`), the writes (`w`addr`=`value), the new
+//! relative base (`b`), and the value it took as input (`i`) or sent as
+//! output (`o`).
+//!
+//! [`replay`] runs a machine against a trace, feeding it the recorded
+//! input, and [`diff`] compares two traces; both report the first step
+//! where they part ways.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Lines, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::devices::input_iter;
+use super::{Hook, Instruction, Intcode, IntcodeError, Memory, OutputSink, RunResult};
+
+const MAGIC: &str = "intcode-trace";
+const VERSION: u32 = 1;
+
+/// Everything one instruction did.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub ip: usize,
+    pub instruction: i64,
+    pub reads: Vec<(usize, i64)>,
+    pub operands: Vec<i64>,
+    pub writes: Vec<(usize, i64)>,
+    pub rb: Option<isize>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    Format { line: usize, message: String },
+}
+
+/// Writes a trace as the machine runs.
+pub struct Tracer<W: Write> {
+    out: W,
+    recorder: Recorder,
+    error: Option<io::Error>,
+}
+
+/// Collects the steps of a run in memory.
+#[derive(Default)]
+struct Recorder {
+    step: Step,
+    done: Option<Step>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Replay {
+    /// The machine did exactly what the trace says, for this many steps.
+    Matched {
+        steps: u64,
+    },
+    Diverged(Box<Divergence>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Steps are numbered from 0.
+    pub step: u64,
+    /// `None` if the trace ended first.
+    pub expected: Option<Step>,
+    pub actual: Actual,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Actual {
+    Step(Step),
+    /// The machine stopped without executing anything.
+    Stopped(RunResult),
+    Fault(IntcodeError),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.ip, self.instruction)?;
+        for (addr, value) in &self.reads {
+            write!(f, " r{addr}={value}")?;
+        }
+        for value in &self.operands {
+            write!(f, " v{value}")?;
+        }
+        for (addr, value) in &self.writes {
+            write!(f, " w{addr}={value}")?;
+        }
+        if let Some(rb) = self.rb {
+            write!(f, " b{rb}")?;
+        }
+        if let Some(value) = self.input {
+            write!(f, " i{value}")?;
+        }
+        if let Some(value) = self.output {
+            write!(f, " o{value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let mut step = Step {
+            ip: parse(words.next().ok_or("missing ip")?)?,
+            instruction: parse(words.next().ok_or("missing instruction")?)?,
+            ..Default::default()
+        };
+
+        for word in words {
+            let (kind, rest) = word.split_at(word.chars().next().map_or(0, char::len_utf8));
+            let pair = || -> Result<(usize, i64), String> {
+                let (addr, value) = rest
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid '{word}'"))?;
+                Ok((parse(addr)?, parse(value)?))
+            };
+
+            match kind {
+                "r" => step.reads.push(pair()?),
+                "v" => step.operands.push(parse(rest)?),
+                "w" => step.writes.push(pair()?),
+                "b" => step.rb = Some(parse(rest)?),
+                "i" => step.input = Some(parse(rest)?),
+                "o" => step.output = Some(parse(rest)?),
+                _ => return Err(format!("invalid '{word}'")),
+            }
+        }
+
+        Ok(step)
+    }
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "{e}"),
+            TraceError::Format { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError::Io(e)
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Replay::Matched { steps } => write!(f, "matched {steps} steps"),
+            Replay::Diverged(d) => {
+                let Divergence {
+                    step,
+                    expected,
+                    actual,
+                } = &**d;
+                writeln!(f, "diverged at step {step}")?;
+                match expected {
+                    Some(s) => writeln!(f, "expected: {s}")?,
+                    None => writeln!(f, "expected: end of trace")?,
+                }
+                match actual {
+                    Actual::Step(s) => write!(f, "actual:   {s}"),
+                    Actual::Stopped(result) => write!(f, "actual:   stopped ({result:?})"),
+                    Actual::Fault(e) => write!(f, "actual:   {e}"),
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        writeln!(out, "{MAGIC} {VERSION}")?;
+        Ok(Self {
+            out,
+            recorder: Recorder::default(),
+            error: None,
+        })
+    }
+
+    /// Flushes the trace and hands back the writer. Reports the first
+    /// error hit while tracing, if any.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl Tracer<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Hook for Tracer<W> {
+    fn read(&mut self, addr: usize, value: i64) {
+        self.recorder.read(addr, value)
+    }
+
+    fn operand(&mut self, value: i64) {
+        self.recorder.operand(value)
+    }
+
+    fn write(&mut self, addr: usize, old: i64, new: i64) {
+        self.recorder.write(addr, old, new)
+    }
+
+    fn relative_base(&mut self, old: isize, new: isize) {
+        self.recorder.relative_base(old, new)
+    }
+
+    fn input(&mut self, value: i64) {
+        self.recorder.input(value)
+    }
+
+    fn output(&mut self, value: i64) {
+        self.recorder.output(value)
+    }
+
+    fn retire(&mut self, ip: usize, instruction: &Instruction) {
+        self.recorder.retire(ip, instruction);
+        let step = self.recorder.done.take().unwrap();
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.out, "{step}") {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn fault(&mut self, error: &IntcodeError) {
+        self.recorder.fault(error)
+    }
+}
+
+impl Hook for Recorder {
+    fn read(&mut self, addr: usize, value: i64) {
+        self.step.reads.push((addr, value));
+    }
+
+    fn operand(&mut self, value: i64) {
+        self.step.operands.push(value);
+    }
+
+    fn write(&mut self, addr: usize, _old: i64, new: i64) {
+        self.step.writes.push((addr, new));
+    }
+
+    fn relative_base(&mut self, _old: isize, new: isize) {
+        self.step.rb = Some(new);
+    }
+
+    fn input(&mut self, value: i64) {
+        self.step.input = Some(value);
+    }
+
+    fn output(&mut self, value: i64) {
+        self.step.output = Some(value);
+    }
+
+    fn retire(&mut self, ip: usize, instruction: &Instruction) {
+        self.step.ip = ip;
+        self.step.instruction = word(instruction);
+        self.done = Some(std::mem::take(&mut self.step));
+    }
+
+    /// The faulting instruction never finished, so whatever it reported
+    /// is not part of the next step.
+    fn fault(&mut self, _error: &IntcodeError) {
+        self.step = Step::default();
+    }
+}
+
+/// Output is already part of each step, so replay throws it away.
+struct Discard;
+
+impl OutputSink for Discard {
+    fn write(&mut self, _value: i64) {}
+}
+
+/// Runs `vm` one instruction at a time against the trace in `r`, feeding
+/// it the input the trace recorded, until the two disagree or the trace
+/// ends and the machine stops.
+pub fn replay<M: Memory, R: BufRead>(vm: &mut Intcode<M>, r: R) -> Result<Replay, TraceError> {
+    let mut trace = TraceReader::new(r)?;
+    let mut steps = 0;
+
+    loop {
+        let expected = trace.next_step()?;
+        let mut input = input_iter(expected.as_ref().and_then(|s| s.input));
+        let mut recorder = Recorder::default();
+
+        let actual = match vm.step_with_hook(&mut input, &mut Discard, &mut recorder) {
+            Ok(RunResult::Ok) => Actual::Step(recorder.done.take().unwrap()),
+            Ok(_) if expected.is_none() => return Ok(Replay::Matched { steps }),
+            Ok(stopped) => Actual::Stopped(stopped),
+            Err(e) => Actual::Fault(e),
+        };
+
+        match (&expected, &actual) {
+            (Some(e), Actual::Step(a)) if e == a => steps += 1,
+            _ => {
+                return Ok(Replay::Diverged(Box::new(Divergence {
+                    step: steps,
+                    expected,
+                    actual,
+                })))
+            }
+        }
+    }
+}
+
+/// The number of the first step where two traces differ, with what each
+/// has there (`None` past its end).
+pub type StepDiff = (u64, Option<Step>, Option<Step>);
+
+/// Compares two traces step by step, returning `None` if they are
+/// identical.
+pub fn diff<A: BufRead, B: BufRead>(a: A, b: B) -> Result<Option<StepDiff>, TraceError> {
+    let mut a = TraceReader::new(a)?;
+    let mut b = TraceReader::new(b)?;
+    let mut n = 0;
+
+    loop {
+        match (a.next_step()?, b.next_step()?) {
+            (None, None) => return Ok(None),
+            (x, y) if x != y => return Ok(Some((n, x, y))),
+            _ => n += 1,
+        }
+    }
+}
+
+struct TraceReader<R> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> TraceReader<R> {
+    fn new(r: R) -> Result<Self, TraceError> {
+        let mut lines = r.lines();
+        let err = |message: String| TraceError::Format { line: 1, message };
+
+        let header = lines
+            .next()
+            .ok_or_else(|| err("empty file".to_string()))??;
+        let version = header
+            .strip_prefix(MAGIC)
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or_else(|| err(format!("not an {MAGIC} file")))?;
+        if version != VERSION {
+            return Err(err(format!("unsupported version {version}")));
+        }
+
+        Ok(Self { lines, line: 1 })
+    }
+
+    fn next_step(&mut self) -> Result<Option<Step>, TraceError> {
+        for line in self.lines.by_ref() {
+            let line = line?;
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            return line
+                .parse()
+                .map(Some)
+                .map_err(|message| TraceError::Format {
+                    line: self.line,
+                    message,
+                });
+        }
+        Ok(None)
+    }
+}
+
+/// The instruction word `ins` was decoded from.
+fn word(ins: &Instruction) -> i64 {
+    let modes = ins
+        .p_mode
+        .iter()
+        .rev()
+        .fold(0, |acc, m| acc * 10 + *m as i64);
+    modes * 100 + ins.opcode as i64
+}
+
+fn parse<T: FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid number '{s}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::QUINE;
+
+    fn record(intcode: &mut Intcode) -> Vec<u8> {
+        let mut tracer = Tracer::new(Vec::new()).unwrap();
+        intcode.try_run_hooked(&mut tracer).unwrap();
+        tracer.finish().unwrap()
+    }
+
+    #[test]
+    fn format() {
+        let trace = record(&mut Intcode::from(QUINE));
+        let text = String::from_utf8(trace).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+
+        assert_eq!(lines.len(), 81);
+        assert_eq!(
+            lines[..6],
+            [
+                "intcode-trace 1",
+                "0 109 v1 b1",
+                "2 204 r0=109 v109 o109",
+                "4 1001 r100=0 v0 v1 w100=1",
+                "8 1008 r100=1 v1 v16 w101=0",
+                "12 1006 r101=0 v0 v0",
+            ]
+        );
+
+        for line in &lines[1..] {
+            assert_eq!(line.parse::<Step>().unwrap().to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn fault_does_not_leak_reads() {
+        // Reads [5], then faults reading [-1].
+        let mut vm = Intcode::from("1,5,-1,0,99,7");
+        let mut tracer = Tracer::new(Vec::new()).unwrap();
+        assert!(vm.try_run_hooked(&mut tracer).is_err());
+
+        vm.set_mem(2, 5);
+        assert_eq!(vm.try_run_hooked(&mut tracer), Ok(RunResult::Halted));
+        let text = String::from_utf8(tracer.finish().unwrap()).unwrap();
+        assert_eq!(text, "intcode-trace 1\n0 1 r5=7 r5=7 v7 v7 w0=14\n");
+    }
+
+    #[test]
+    fn replay_matches() {
+        let trace = record(&mut Intcode::from(QUINE));
+        let result = replay(&mut Intcode::from(QUINE), &trace[..]).unwrap();
+        assert_eq!(result, Replay::Matched { steps: 80 });
+    }
+
+    #[test]
+    fn replay_feeds_recorded_input() {
+        let mut intcode = Intcode::from("3,0,4,0,99");
+        intcode.input(42);
+        let trace = record(&mut intcode);
+        assert_eq!(
+            String::from_utf8(trace.clone()).unwrap(),
+            "intcode-trace 1\n0 3 w0=42 i42\n2 4 r0=42 v42 o42\n"
+        );
+
+        let mut fresh = Intcode::from("3,0,4,0,99");
+        assert_eq!(
+            replay(&mut fresh, &trace[..]).unwrap(),
+            Replay::Matched { steps: 2 }
+        );
+        assert_eq!(fresh.output(), None);
+    }
+
+    #[test]
+    fn replay_diverges() {
+        let trace = record(&mut Intcode::from(QUINE));
+
+        // Counts to 15 instead of 16. Only the immediate operand of the
+        // first comparison differs; everything it reads and writes agrees.
+        let changed = QUINE.replace(",16,", ",15,");
+        let result = replay(&mut Intcode::from(changed.as_str()), &trace[..]).unwrap();
+        let Replay::Diverged(d) = &result else {
+            panic!("{result}");
+        };
+        assert_eq!(d.step, 3);
+        assert_eq!(
+            d.expected,
+            Some("8 1008 r100=1 v1 v16 w101=0".parse().unwrap())
+        );
+        assert_eq!(
+            d.actual,
+            Actual::Step("8 1008 r100=1 v1 v15 w101=0".parse().unwrap())
+        );
+        assert_eq!(
+            result.to_string(),
+            "diverged at step 3\n\
+             expected: 8 1008 r100=1 v1 v16 w101=0\n\
+             actual:   8 1008 r100=1 v1 v15 w101=0"
+        );
+
+        // Running past the end of the trace.
+        let short = b"intcode-trace 1\n0 109 v1 b1\n";
+        let result = replay(&mut Intcode::from(QUINE), &short[..]).unwrap();
+        let Replay::Diverged(d) = result else {
+            panic!("{result}");
+        };
+        assert_eq!((d.step, &d.expected), (1, &None));
+        assert!(matches!(d.actual, Actual::Step(_)));
+
+        // Stopping before it.
+        let result = replay(&mut Intcode::from("99"), &short[..]).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "diverged at step 0\n\
+             expected: 0 109 v1 b1\n\
+             actual:   stopped (Halted)"
+        );
+    }
+
+    #[test]
+    fn diff_traces() {
+        let a = record(&mut Intcode::from(QUINE));
+        assert_eq!(diff(&a[..], &a[..]).unwrap(), None);
+
+        let b = record(&mut Intcode::from(QUINE.replace(",16,", ",15,").as_str()));
+        let (step, x, y) = diff(&a[..], &b[..]).unwrap().unwrap();
+        assert_eq!(step, 3);
+        assert_eq!(x.unwrap().operands, [1, 16]);
+        assert_eq!(y.unwrap().operands, [1, 15]);
+    }
+
+    #[test]
+    fn errors() {
+        let read = |s: &str| replay(&mut Intcode::from("99"), s.as_bytes()).unwrap_err();
+
+        assert_eq!(read("").to_string(), "line 1: empty file");
+        assert_eq!(
+            read("intcode-trace 2\n").to_string(),
+            "line 1: unsupported version 2"
+        );
+        assert_eq!(
+            read("intcode-trace 1\n0 99 x5\n").to_string(),
+            "line 2: invalid 'x5'"
+        );
+        assert_eq!(
+            read("intcode-trace 1\n\n0 1 rfoo\n").to_string(),
+            "line 3: invalid 'rfoo'"
+        );
+    }
+}
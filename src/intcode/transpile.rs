@@ -0,0 +1,611 @@
+//! Compiling a fixed program into Rust source.
+//!
+//! [`transpile`] emits a self-contained module defining a `Machine` with
+//! the same input and output behaviour as [`Intcode`]: `input`, `output`,
+//! `get_mem`, `set_mem`, `ip` and `try_run`, which stops when the program
+//! halts or needs input and can be called again after more input is
+//! queued. Faults are reported as a `Fault` holding the instruction
+//! pointer.
+//!
+//! Each basic block found by [`Cfg`] becomes a match arm of straight-line
+//! Rust. Everything else runs on a small interpreter embedded in the
+//! module: code only reachable through dynamic jumps, blocks that the
+//! program statically writes into, resuming in the middle of a block,
+//! and anything unusual such as an overflow, which the interpreter then
+//! reports as a fault. If the program ever changes compiled code, the
+//! machine switches to the interpreter for good.
+
+use std::fmt::Write;
+
+use super::cfg::{Cfg, Exit, Target};
+use super::disasm::{Item, Line, Operand};
+use super::memory::DENSE_SLACK;
+use super::{Intcode, Memory, Opcode};
+
+const RUNTIME: &str = r#"
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RunResult {
+    Halted,
+    BlockedOnInput,
+}
+
+/// The instruction at `ip` faulted.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Fault {
+    pub ip: usize,
+}
+
+// Not every program has blocks that leave in both ways.
+#[allow(dead_code)]
+enum Flow {
+    Next,
+    Stop(RunResult),
+}
+
+#[derive(Clone)]
+pub struct Machine {
+    mem: Vec<i64>,
+    /// Writes far beyond the end of `mem`, kept apart so that a stray
+    /// address does not force a huge allocation.
+    far: HashMap<usize, i64>,
+    ip: usize,
+    rb: i64,
+    input: VecDeque<i64>,
+    output: VecDeque<i64>,
+    interpret: bool,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        Self {
+            mem: IMAGE.to_vec(),
+            far: HashMap::new(),
+            ip: 0,
+            rb: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            interpret: false,
+        }
+    }
+
+    pub fn input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    pub fn output(&mut self) -> Option<i64> {
+        self.output.pop_front()
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn get_mem(&self, addr: usize) -> i64 {
+        match self.mem.get(addr) {
+            Some(n) => *n,
+            None => self.far.get(&addr).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn set_mem(&mut self, addr: usize, value: i64) {
+        self.store(addr, value);
+    }
+
+    pub fn try_run(&mut self) -> Result<RunResult, Fault> {
+        loop {
+            let flow = if self.interpret { None } else { self.compiled() };
+
+            match flow {
+                Some(Flow::Next) => {}
+                Some(Flow::Stop(result)) => return Ok(result),
+                None => {
+                    let ip = self.ip;
+                    match self.step() {
+                        Some(Some(result)) => return Ok(result),
+                        Some(None) => {}
+                        None => return Err(Fault { ip }),
+                    }
+                }
+            }
+        }
+    }
+
+    fn load(&self, addr: i64) -> Option<i64> {
+        Some(self.get_mem(to_addr(addr)?))
+    }
+
+    fn rel(&self, n: i64) -> Option<i64> {
+        self.rb.checked_add(n)
+    }
+
+    /// Writes `value` to `addr`, returning whether compiled code can no
+    /// longer be trusted.
+    fn store(&mut self, addr: usize, value: i64) -> bool {
+        if addr >= self.mem.len() {
+            if addr >= self.mem.len() * 2 + SLACK {
+                self.far.insert(addr, value);
+                return self.interpret;
+            }
+            let len = (addr + 1).max(self.mem.len() * 2);
+            self.mem.resize(len, 0);
+            let moved: Vec<usize> = self.far.keys().copied().filter(|a| *a < len).collect();
+            for a in moved {
+                self.mem[a] = self.far.remove(&a).unwrap();
+            }
+        }
+        let old = std::mem::replace(&mut self.mem[addr], value);
+
+        if old != value && !self.interpret {
+            let i = COMPILED.partition_point(|&(_, end)| end <= addr);
+            self.interpret = COMPILED.get(i).is_some_and(|&(start, _)| start <= addr);
+        }
+        self.interpret
+    }
+
+    fn read(&self, mode: i64, param: usize) -> Option<i64> {
+        let n = self.get_mem(self.ip + param + 1);
+        match mode {
+            0 => self.load(n),
+            1 => Some(n),
+            _ => self.load(self.rel(n)?),
+        }
+    }
+
+    fn dst(&self, mode: i64, param: usize) -> Option<usize> {
+        let n = self.get_mem(self.ip + param + 1);
+        match mode {
+            0 => to_addr(n),
+            1 => None,
+            _ => to_addr(self.rel(n)?),
+        }
+    }
+
+    /// Interprets one instruction. Returns `None` on a fault.
+    fn step(&mut self) -> Option<Option<RunResult>> {
+        let word = self.get_mem(self.ip);
+        if !(0..100_000).contains(&word) {
+            return None;
+        }
+        let m = [word / 100 % 10, word / 1_000 % 10, word / 10_000];
+        if m.iter().any(|&m| m > 2) {
+            return None;
+        }
+
+        match word % 100 {
+            op @ (1 | 2 | 7 | 8) => {
+                let (a, b, d) = (self.read(m[0], 0)?, self.read(m[1], 1)?, self.dst(m[2], 2)?);
+                let value = match op {
+                    1 => a.checked_add(b)?,
+                    2 => a.checked_mul(b)?,
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+                self.store(d, value);
+                self.ip += 4;
+            }
+            3 => {
+                let d = self.dst(m[0], 0)?;
+                match self.input.pop_front() {
+                    Some(value) => self.store(d, value),
+                    None => return Some(Some(RunResult::BlockedOnInput)),
+                };
+                self.ip += 2;
+            }
+            4 => {
+                let value = self.read(m[0], 0)?;
+                self.output.push_back(value);
+                self.ip += 2;
+            }
+            op @ (5 | 6) => {
+                let c = self.read(m[0], 0)?;
+                if (c != 0) == (op == 5) {
+                    self.ip = to_addr(self.read(m[1], 1)?)?;
+                } else {
+                    self.ip += 3;
+                }
+            }
+            9 => {
+                self.rb = self.rb.checked_add(self.read(m[0], 0)?)?;
+                self.ip += 2;
+            }
+            99 => return Some(Some(RunResult::Halted)),
+            _ => return None,
+        }
+        Some(None)
+    }
+"#;
+
+/// Emits a Rust module running `program`. See the module documentation
+/// for what it contains.
+pub fn transpile(program: &[i64]) -> String {
+    let cfg = Cfg::new(program);
+    let blocks: Vec<_> = cfg
+        .blocks()
+        .filter(|block| {
+            !cfg.self_writes()
+                .iter()
+                .any(|w| (block.start..block.end).contains(&w.target))
+        })
+        .collect();
+
+    let mut out = String::new();
+    out.push_str(
+        "// Generated from an Intcode program by adventofcode_2019::intcode::transpile.\n\n",
+    );
+    out.push_str("use std::collections::{HashMap, VecDeque};\n\n");
+    writeln!(
+        out,
+        "/// How far past the end of memory a write may land and still grow it.\n\
+         const SLACK: usize = {DENSE_SLACK};\n"
+    )
+    .unwrap();
+
+    out.push_str("const IMAGE: &[i64] = &[");
+    for (i, word) in program.iter().enumerate() {
+        let sep = if i % 16 == 0 { "\n    " } else { " " };
+        write!(out, "{sep}{word},").unwrap();
+    }
+    out.push_str("\n];\n\n");
+
+    out.push_str("/// Address ranges of compiled code.\n");
+    out.push_str("const COMPILED: &[(usize, usize)] = &[");
+    for block in &blocks {
+        write!(out, "({}, {}), ", block.start, block.end).unwrap();
+    }
+    out.truncate(out.trim_end_matches(", ").len());
+    out.push_str("];\n");
+
+    out.push_str(RUNTIME);
+    out.push_str(
+        "
+    fn compiled(&mut self) -> Option<Flow> {
+        match self.ip {
+",
+    );
+    for block in blocks {
+        writeln!(out, "            {} => {{", block.start).unwrap();
+        for line in &block.lines {
+            instruction(&mut out, line);
+        }
+        exit(&mut out, block.lines.last().unwrap(), block.exit);
+        out.push_str("            }\n");
+    }
+    out.push_str(
+        "            _ => None,
+        }
+    }
+}
+
+fn to_addr(n: i64) -> Option<usize> {
+    usize::try_from(n).ok()
+}
+",
+    );
+    out
+}
+
+impl<M: Memory> Intcode<M> {
+    /// Transpiles everything from address 0 up to the highest address that
+    /// has been loaded or written.
+    pub fn transpile(&self) -> String {
+        transpile(&self.get_mem_range(0, self.memory.end()))
+    }
+}
+
+const INDENT: &str = "                ";
+
+/// Emits the body of any instruction except one that ends its block.
+fn instruction(out: &mut String, line: &Line) {
+    let Item::Instruction { opcode, operands } = &line.item else {
+        unreachable!("blocks hold only instructions");
+    };
+    let next = line.addr + line.words.len();
+
+    writeln!(out, "{INDENT}// {}: {}", line.addr, line.item).unwrap();
+    writeln!(out, "{INDENT}self.ip = {};", line.addr).unwrap();
+
+    let store = |out: &mut String, value: &str| {
+        writeln!(out, "{INDENT}if self.store(d, {value}) {{").unwrap();
+        writeln!(out, "{INDENT}    self.ip = {next};").unwrap();
+        writeln!(out, "{INDENT}    return Some(Flow::Next);").unwrap();
+        writeln!(out, "{INDENT}}}").unwrap();
+    };
+
+    match opcode {
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+            writeln!(out, "{INDENT}let a: i64 = {};", read(operands[0])).unwrap();
+            writeln!(out, "{INDENT}let b: i64 = {};", read(operands[1])).unwrap();
+            writeln!(out, "{INDENT}let d = {};", dst(operands[2])).unwrap();
+            let value = match opcode {
+                Opcode::Add => "a.checked_add(b)?",
+                Opcode::Multiply => "a.checked_mul(b)?",
+                Opcode::LessThan => "(a < b) as i64",
+                _ => "(a == b) as i64",
+            };
+            store(out, value);
+        }
+        Opcode::Input => {
+            writeln!(out, "{INDENT}let d = {};", dst(operands[0])).unwrap();
+            writeln!(
+                out,
+                "{INDENT}let Some(value) = self.input.pop_front() else {{"
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "{INDENT}    return Some(Flow::Stop(RunResult::BlockedOnInput));"
+            )
+            .unwrap();
+            writeln!(out, "{INDENT}}};").unwrap();
+            store(out, "value");
+        }
+        Opcode::Output => {
+            writeln!(out, "{INDENT}let a: i64 = {};", read(operands[0])).unwrap();
+            writeln!(out, "{INDENT}self.output.push_back(a);").unwrap();
+        }
+        Opcode::RelativeBase => {
+            writeln!(out, "{INDENT}let a: i64 = {};", read(operands[0])).unwrap();
+            writeln!(out, "{INDENT}self.rb = self.rb.checked_add(a)?;").unwrap();
+        }
+        // Jumps that end a block are handled by `exit`; any other is never
+        // taken.
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt => {}
+    }
+}
+
+/// Emits the code leaving a block whose last instruction is `last`.
+fn exit(out: &mut String, last: &Line, exit: Exit) {
+    let Item::Instruction { opcode, operands } = &last.item else {
+        unreachable!("blocks hold only instructions");
+    };
+
+    let jump = |target: Target| match target {
+        Target::Static(t) if t >= 0 => format!("self.ip = {t};"),
+        Target::Static(_) => "return None;".to_string(),
+        Target::Dynamic => format!("self.ip = to_addr({})?;", read(operands[1])),
+    };
+
+    match exit {
+        Exit::Next(next) => {
+            writeln!(out, "{INDENT}self.ip = {next};").unwrap();
+            writeln!(out, "{INDENT}Some(Flow::Next)").unwrap();
+        }
+        Exit::Branch { target, next } => {
+            let taken = if *opcode == Opcode::JumpIfTrue {
+                "!="
+            } else {
+                "=="
+            };
+            writeln!(out, "{INDENT}let c: i64 = {};", read(operands[0])).unwrap();
+            writeln!(out, "{INDENT}if c {taken} 0 {{").unwrap();
+            writeln!(out, "{INDENT}    {}", jump(target)).unwrap();
+            writeln!(out, "{INDENT}}} else {{").unwrap();
+            writeln!(out, "{INDENT}    self.ip = {next};").unwrap();
+            writeln!(out, "{INDENT}}}").unwrap();
+            writeln!(out, "{INDENT}Some(Flow::Next)").unwrap();
+        }
+        Exit::Jump(Target::Static(t)) if t < 0 => writeln!(out, "{INDENT}None").unwrap(),
+        Exit::Jump(target) => {
+            writeln!(out, "{INDENT}{}", jump(target)).unwrap();
+            writeln!(out, "{INDENT}Some(Flow::Next)").unwrap();
+        }
+        Exit::Halt => writeln!(out, "{INDENT}Some(Flow::Stop(RunResult::Halted))").unwrap(),
+    }
+}
+
+fn read(op: Operand) -> String {
+    match op {
+        Operand::Position(n) if n >= 0 => format!("self.get_mem({n})"),
+        Operand::Position(n) => format!("self.load({n})?"),
+        Operand::Immediate(n) => n.to_string(),
+        Operand::Relative(n) => format!("self.load(self.rel({n})?)?"),
+    }
+}
+
+fn dst(op: Operand) -> String {
+    match op {
+        Operand::Position(n) if n >= 0 => n.to_string(),
+        Operand::Position(n) => format!("to_addr({n})?"),
+        Operand::Immediate(_) => unreachable!("immediate writes do not decode"),
+        Operand::Relative(n) => format!("to_addr(self.rel({n})?)?"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::intcode::{IntcodeError, RunResult};
+    use std::fs;
+    use std::process::Command;
+
+    /// Example programs from days 5, 7 and 9, plus a few that fault or
+    /// modify themselves, with rounds of input to feed each one.
+    const CASES: &[(&str, &[&[i64]])] = &[
+        // Day 5.
+        ("3,0,4,0,99", &[&[17]]),
+        ("1002,4,3,4,33", &[]),
+        ("3,9,8,9,10,9,4,9,99,-1,8", &[&[8], &[]]),
+        ("3,9,7,9,10,9,4,9,99,-1,8", &[&[3]]),
+        ("3,3,1108,-1,8,3,4,3,99", &[&[7]]),
+        ("3,3,1107,-1,8,3,4,3,99", &[&[9]]),
+        ("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[&[0]]),
+        ("3,3,1105,-1,9,1101,0,0,12,4,12,99,1", &[&[5]]),
+        (
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+             1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+             999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+            &[&[], &[7]],
+        ),
+        // Day 7.
+        ("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0", &[&[4, 0]]),
+        (
+            "3,23,3,24,1002,24,10,24,1002,23,-1,23,\
+             101,5,23,23,1,24,23,23,4,23,99,0,0",
+            &[&[0], &[1]],
+        ),
+        (
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+             27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+            &[&[9, 0], &[1], &[2], &[3], &[4], &[5]],
+        ),
+        // Day 9.
+        (
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            &[],
+        ),
+        ("1102,34915192,34915192,7,4,7,99,0", &[]),
+        ("104,1125899906842624,99", &[]),
+        // Day 2, which overwrites its own code.
+        ("1,9,10,3,2,3,11,0,99,30,40,50", &[]),
+        // A write and read far beyond the end of the program.
+        ("1101,5,0,1000000000000,4,1000000000000,99", &[]),
+        // Overflow, a negative jump and a jump into data.
+        ("1102,4611686018427387904,2,0,99", &[]),
+        ("3,7,1005,7,-1,99,0", &[&[1]]),
+        ("1105,1,3,42", &[]),
+    ];
+
+    fn describe(result: Result<RunResult, IntcodeError>) -> String {
+        match result {
+            Ok(RunResult::Halted) => "halted".to_string(),
+            Ok(RunResult::BlockedOnInput) => "blocked".to_string(),
+            Ok(other) => unreachable!("{other:?} without a budget"),
+            Err(e) => format!("fault at {}", e.ip),
+        }
+    }
+
+    fn interpret(program: &str, rounds: &[&[i64]]) -> String {
+        let mut intcode = Intcode::from(program);
+        let mut text = String::new();
+
+        for round in [&[][..]].iter().chain(rounds) {
+            round.iter().for_each(|v| intcode.input(*v));
+            let result = describe(intcode.try_run());
+            let output: Vec<i64> = std::iter::from_fn(|| intcode.output()).collect();
+            writeln!(text, "{result} {output:?} {}", intcode.ip()).unwrap();
+        }
+        text
+    }
+
+    /// A program printing, for each case, what `interpret` would.
+    fn driver(cases: &[(String, Vec<Vec<i64>>)]) -> String {
+        let mut main = String::new();
+
+        for (i, (program, _)) in cases.iter().enumerate() {
+            let intcode = Intcode::from(program.as_str());
+            writeln!(main, "pub mod p{i} {{\n{}}}", intcode.transpile()).unwrap();
+        }
+
+        main.push_str("fn main() {\n");
+        for (i, (_, rounds)) in cases.iter().enumerate() {
+            writeln!(main, "    let mut m = p{i}::Machine::new();").unwrap();
+            for round in [&[][..]]
+                .into_iter()
+                .chain(rounds.iter().map(Vec::as_slice))
+            {
+                writeln!(
+                    main,
+                    "    for v in {round:?} {{ m.input(v); }}
+    let result = match m.try_run() {{
+        Ok(p{i}::RunResult::Halted) => \"halted\".to_string(),
+        Ok(p{i}::RunResult::BlockedOnInput) => \"blocked\".to_string(),
+        Err(f) => format!(\"fault at {{}}\", f.ip),
+    }};
+    let output: Vec<i64> = std::iter::from_fn(|| m.output()).collect();
+    println!(\"{{result}} {{output:?}} {{}}\", m.ip());"
+                )
+                .unwrap();
+            }
+            main.push_str("    println!(\"--\");\n");
+        }
+        main.push_str("}\n");
+        main
+    }
+
+    /// Compiles the transpiled `cases` with rustc and checks that each
+    /// behaves as it does on the interpreter.
+    fn compare(name: &str, cases: &[(String, Vec<Vec<i64>>)]) {
+        let dir =
+            std::env::temp_dir().join(format!("intcode-transpile-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.rs");
+        let binary = dir.join("main");
+        fs::write(&source, driver(cases)).unwrap();
+
+        let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+        let status = Command::new(rustc)
+            .args(["--edition", "2021", "-D", "warnings", "-o"])
+            .arg(&binary)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "generated code does not compile");
+
+        let output = Command::new(&binary).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+
+        let compiled: Vec<&str> = output.split("--\n").collect();
+        for (i, (program, rounds)) in cases.iter().enumerate() {
+            let rounds: Vec<&[i64]> = rounds.iter().map(Vec::as_slice).collect();
+            assert_eq!(compiled[i], interpret(program, &rounds), "{program}");
+        }
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        let cases: Vec<_> = CASES
+            .iter()
+            .map(|(program, rounds)| {
+                let rounds = rounds.iter().map(|round| round.to_vec()).collect();
+                (program.to_string(), rounds)
+            })
+            .collect();
+        compare("examples", &cases);
+    }
+
+    #[test]
+    fn matches_interpreter_on_inputs() {
+        // Day 5 with both system IDs, day 7 in both amplifier modes and
+        // day 9 in test and boost mode.
+        let runs: [(u32, &[&[&[i64]]]); 3] = [
+            (5, &[&[&[1]], &[&[5]]]),
+            (7, &[&[&[0, 0]], &[&[9, 0], &[1], &[2]]]),
+            (9, &[&[&[1]], &[&[2]]]),
+        ];
+
+        let mut cases = Vec::new();
+        for (day, runs) in runs {
+            let Some(program) = input::for_test(day) else {
+                continue;
+            };
+            for rounds in runs {
+                let rounds = rounds.iter().map(|round| round.to_vec()).collect();
+                cases.push((program.clone(), rounds));
+            }
+        }
+        if !cases.is_empty() {
+            compare("inputs", &cases);
+        }
+    }
+
+    #[test]
+    fn regions() {
+        // Compare to 8: one block per branch.
+        let source = transpile(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        assert!(source.contains("const COMPILED: &[(usize, usize)] = &[(0, 9)];"));
+        assert!(source.contains("            0 => {\n                // 0: in [9]\n"));
+
+        // Day 2 writes into its only block, so nothing is compiled.
+        let source = transpile(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert!(source.contains("const COMPILED: &[(usize, usize)] = &[];"));
+        assert!(!source.contains("            0 => {"));
+    }
+}
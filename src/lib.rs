@@ -8,4 +8,11 @@ pub mod day7;
 //pub mod day8;
 pub mod day9;
 
+pub mod answers;
+pub mod input;
 pub mod intcode;
+pub mod parse;
+pub mod solution;
+
+pub use parse::ParseError;
+pub use solution::Solution;
@@ -0,0 +1,88 @@
+//! Reporting malformed input.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where parsing failed, what was found there and what was expected.
+/// Lines and columns count from 1, columns in characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub expected: &'static str,
+}
+
+impl ParseError {
+    /// An error at `text`, which must be a slice of `input`. An empty
+    /// slice, such as `&input[input.len()..]`, points between characters.
+    pub fn at(input: &str, text: &str, expected: &'static str) -> Self {
+        let offset = (text.as_ptr() as usize).wrapping_sub(input.as_ptr() as usize);
+        assert!(offset <= input.len(), "text is not part of the input");
+
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            text: text.to_string(),
+            expected,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected {}, found ",
+            self.line, self.column, self.expected
+        )?;
+        match self.text.as_str() {
+            "" => write!(f, "nothing"),
+            text => write!(f, "'{text}'"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parses `text`, a slice of `input`, as a number.
+pub fn number<T: FromStr>(input: &str, text: &str) -> Result<T, ParseError> {
+    text.parse()
+        .map_err(|_| ParseError::at(input, text, "a number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let input = "12\n3x4\n\n";
+        assert_eq!(number::<i32>(input, &input[..2]), Ok(12));
+
+        let e = number::<i32>(input, &input[3..6]).unwrap_err();
+        assert_eq!((e.line, e.column, e.text.as_str()), (2, 1, "3x4"));
+        assert_eq!(
+            e.to_string(),
+            "line 2, column 1: expected a number, found '3x4'"
+        );
+
+        let e = ParseError::at(input, &input[4..5], "a digit");
+        assert_eq!((e.line, e.column), (2, 2));
+
+        let e = ParseError::at(input, &input[input.len()..], "a wire");
+        assert_eq!((e.line, e.column), (4, 1));
+        assert_eq!(
+            e.to_string(),
+            "line 4, column 1: expected a wire, found nothing"
+        );
+
+        let input = "é,x";
+        let e = number::<i32>(input, &input[3..]).unwrap_err();
+        assert_eq!(e.column, 3);
+    }
+}
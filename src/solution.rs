@@ -0,0 +1,143 @@
+//! What every day has in common, and a registry of the days solved so far.
+//!
+//! Each `dayN` module has a `Puzzle` implementing [`Solution`]. [`days`]
+//! lists them so runners and tests can go through every day without
+//! knowing the answer types, which they see as text through [`Answers`].
+
+use std::fmt::{self, Display};
+
+use crate::{day1, day2, day3, day4, day5, day7, day9, ParseError};
+
+pub trait Solution<'a>: Sized {
+    type Answer1: Display;
+    type Answer2: Display;
+
+    fn new(input: &'a str) -> Result<Self, ParseError>;
+    fn solve(&self) -> Self::Answer1;
+    fn solve2(&self) -> Self::Answer2;
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub enum Part {
+    One = 1,
+    Two = 2,
+}
+
+/// A parsed puzzle whose answers are rendered as text.
+pub trait Answers {
+    fn answer(&self, part: Part) -> String;
+}
+
+/// A day in the registry.
+pub struct Day {
+    pub day: u32,
+    parse: for<'a> fn(&'a str) -> Result<Box<dyn Answers + 'a>, ParseError>,
+}
+
+impl Part {
+    pub const ALL: [Part; 2] = [Part::One, Part::Two];
+}
+
+impl TryFrom<u32> for Part {
+    type Error = u32;
+
+    fn try_from(n: u32) -> Result<Self, Self::Error> {
+        match n {
+            1 => Ok(Part::One),
+            2 => Ok(Part::Two),
+            other => Err(other),
+        }
+    }
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (*self as u32).fmt(f)
+    }
+}
+
+impl<'a, S: Solution<'a>> Answers for S {
+    fn answer(&self, part: Part) -> String {
+        match part {
+            Part::One => self.solve().to_string(),
+            Part::Two => self.solve2().to_string(),
+        }
+    }
+}
+
+impl Day {
+    pub fn parse<'a>(&self, input: &'a str) -> Result<Box<dyn Answers + 'a>, ParseError> {
+        (self.parse)(input)
+    }
+
+    pub fn solve(&self, input: &str, part: Part) -> Result<String, ParseError> {
+        Ok(self.parse(input)?.answer(part))
+    }
+}
+
+macro_rules! days {
+    ($($day:literal => $module:ident),* $(,)?) => {
+        const DAYS: &[Day] = &[$(
+            Day {
+                day: $day,
+                parse: {
+                    fn parse(input: &str) -> Result<Box<dyn Answers + '_>, ParseError> {
+                        Ok(Box::new($module::Puzzle::new(input)?))
+                    }
+                    parse
+                },
+            },
+        )*];
+    };
+}
+
+days! {
+    1 => day1,
+    2 => day2,
+    3 => day3,
+    4 => day4,
+    5 => day5,
+    7 => day7,
+    9 => day9,
+}
+
+/// Every solved day, in order.
+pub fn days() -> &'static [Day] {
+    DAYS
+}
+
+pub fn day(day: u32) -> Option<&'static Day> {
+    DAYS.iter().find(|d| d.day == day)
+}
+
+/// The solver for one part of a day, taking the puzzle input.
+pub fn solver(day: u32, part: Part) -> Option<impl Fn(&str) -> Result<String, ParseError>> {
+    let day = self::day(day)?;
+    Some(move |input: &str| day.solve(input, part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        let numbers: Vec<u32> = days().iter().map(|d| d.day).collect();
+        assert_eq!(numbers, vec![1, 2, 3, 4, 5, 7, 9]);
+        assert!(day(6).is_none());
+        assert!(solver(25, Part::One).is_none());
+        assert_eq!(Part::try_from(3), Err(3));
+
+        let fuel = solver(1, Part::One).unwrap();
+        assert_eq!(fuel("12\n14\n1969\n100756"), Ok("34241".to_string()));
+        let fuel = solver(1, Part::Two).unwrap();
+        assert_eq!(fuel("14\n1969"), Ok("968".to_string()));
+        assert_eq!(fuel("14\n19x69").unwrap_err().line, 2);
+
+        let wires = day(3).unwrap().parse("R8,U5,L5,D3\nU7,R6,D4,L4").unwrap();
+        assert_eq!(
+            Part::ALL.map(|part| wires.answer(part)),
+            ["6".to_string(), "30".to_string()]
+        );
+    }
+}